@@ -10,21 +10,21 @@ pub fn parse_regex<'a>(regex_string: &str, arena: &'a Corrida) -> Result<Nfa<'a
     let create_state = |is_final| new_state!(is_final);
     
     fn parse_base<'a>(cur: &'a mut RState, chars: &mut Peekable<Chars>, create_state: &impl Fn(bool) -> &'a mut State<2, char>) -> Result<(&'a mut RState, &'a mut RState), &'static str> {
-        let (base_start, base_end) = match chars.next() {
+        enum Base<'a> {
+            Symbol(char),
+            Group(&'a mut RState, &'a mut RState),
+        }
+
+        let base = match chars.next() {
             Some('(') => {
                 let (start_node, end_state) = parse_group::<false>(chars, create_state)?;
-                cur.push_transition(None, Some(start_node));
-                (cur, end_state)
+                Base::Group(start_node, end_state)
             },
             Some(c) => {
                 if c == '+' || c == '*' || c == '?' {
                     return Err("Got an operator (+, *, ?) when there was no base to skip/repeat");
                 }
-
-                let new_state = create_state(false);
-                cur.push_transition(Some(c), Some(new_state));
-
-                (cur, new_state)
+                Base::Symbol(c)
             },
             None => {
                 panic!("How did we get here.")
@@ -55,14 +55,43 @@ pub fn parse_regex<'a>(regex_string: &str, arena: &'a Corrida) -> Result<Nfa<'a
             chars.next(); //eat
         }
 
+        // Without operators the base hangs directly off the current state.
+        if !add_cycle && !add_skip {
+            let base_end = match base {
+                Base::Symbol(c) => {
+                    let new_state = create_state(false);
+                    cur.push_transition(Some(c), Some(new_state));
+                    new_state
+                },
+                Base::Group(start_node, end_state) => {
+                    cur.push_transition(None, Some(start_node));
+                    end_state
+                },
+            };
+            return Ok((cur, base_end));
+        }
+
+        // With operators the base gets start and end states of its own, so the cycle can not reach the bases before it and the skip can not enter loops inside it.
+        let (base_start, inner_end) = match base {
+            Base::Symbol(c) => {
+                let (start_node, new_state) = (create_state(false), create_state(false));
+                start_node.push_transition(Some(c), Some(new_state));
+                (start_node, new_state)
+            },
+            Base::Group(start_node, end_state) => (start_node, end_state),
+        };
+        let base_end = create_state(false);
+        inner_end.push_transition(None, Some(base_end));
+
         if add_cycle {
-            base_end.push_transition(None, Some(base_start));
+            inner_end.push_transition(None, Some(base_start));
         }
         if add_skip {
             base_start.push_transition(None, Some(base_end));
         }
+        cur.push_transition(None, Some(base_start));
 
-        Ok((base_start, base_end))
+        Ok((cur, base_end))
     }
 
     fn parse_concat<'a>(chars: &mut Peekable<Chars>,create_state: &impl Fn(bool) -> &'a mut State<2, char>) -> Result<(&'a mut RState, Option<&'a mut RState>), &'static str> {
@@ -147,15 +176,15 @@ mod tests {
         let arena = Corrida::new(None);
         let nfa = parse_regex("ab*(c|)", &arena).unwrap();
         
-        assert_eq!(nfa.simulate_iter("".chars()), false);
-        assert_eq!(nfa.simulate_iter("a".chars()), true);
-        assert_eq!(nfa.simulate_iter("ab".chars()), true);
-        assert_eq!(nfa.simulate_iter("ac".chars()), true);
-        assert_eq!(nfa.simulate_iter("abb".chars()), true);
-        assert_eq!(nfa.simulate_iter("abbcc".chars()), false);
-        assert_eq!(nfa.simulate_iter("abbbac".chars()), false);
-        assert_eq!(nfa.simulate_iter("abaa".chars()), false);
-        assert_eq!(nfa.simulate_iter("abbbbbbbc".chars()), true);
+        assert!(!nfa.simulate_iter("".chars()));
+        assert!(nfa.simulate_iter("a".chars()));
+        assert!(nfa.simulate_iter("ab".chars()));
+        assert!(nfa.simulate_iter("ac".chars()));
+        assert!(nfa.simulate_iter("abb".chars()));
+        assert!(!nfa.simulate_iter("abbcc".chars()));
+        assert!(!nfa.simulate_iter("abbbac".chars()));
+        assert!(!nfa.simulate_iter("abaa".chars()));
+        assert!(nfa.simulate_iter("abbbbbbbc".chars()));
     }    
 
     #[test]
@@ -165,18 +194,18 @@ mod tests {
 
         let mut test = vec!['b'; 100_000];
         let start = Instant::now();
-        assert_eq!(nfa.simulate_slice(&test), false);
+        assert!(!nfa.simulate_slice(&test));
         test.push('a');
-        assert_eq!(nfa.simulate_slice(&test), true);
+        assert!(nfa.simulate_slice(&test));
         let a = start.elapsed();
 
         test.pop();
         let dfa = nfa.as_dfa(&arena);
 
         let start = Instant::now();
-        assert_eq!(dfa.simulate_slice(&test), false);
+        assert!(!dfa.simulate_slice(&test));
         test.push('a');
-        assert_eq!(dfa.simulate_slice(&test), true);
+        assert!(dfa.simulate_slice(&test));
         let b = start.elapsed();
 
         println!("Unfriendly -- NFA {:?}, DFA {:?}", a, b);
@@ -231,4 +260,50 @@ mod tests {
 
         println!("a?^na^n -- NFA {:?}, NFA Friendly {:?}, DFA {:?}", a, "N/A", c);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_repeated_bases() {
+        let arena = Corrida::new(None);
+        let nfa = parse_regex("a*b*", &arena).unwrap();
+        assert!(nfa.simulate_iter("aabb".chars()));
+        assert!(!nfa.simulate_iter("ba".chars()));
+
+        let nfa = parse_regex("(ab)+c?d", &arena).unwrap();
+        assert!(nfa.simulate_iter("ababd".chars()));
+        assert!(nfa.simulate_iter("abcd".chars()));
+        assert!(!nfa.simulate_iter("abcabd".chars()));
+        assert!(!nfa.simulate_iter("d".chars()));
+
+        let nfa = parse_regex("(a(b|c)*)?a", &arena).unwrap();
+        assert!(nfa.simulate_iter("abca".chars()));
+        assert!(nfa.simulate_iter("a".chars()));
+        assert!(!nfa.simulate_iter("ba".chars()));
+    }
+
+    #[test]
+    pub fn test_to_regex_round_trip() {
+        let words: Vec<String> = (0..=6)
+            .flat_map(|len| (0..3usize.pow(len)).map(move |mut word| {
+                (0..len).map(|_| {
+                    let symbol = ['a', 'b', 'c'][word % 3];
+                    word /= 3;
+                    symbol
+                }).collect()
+            }))
+            .collect();
+
+        for regex in ["", "a", "ab*(c|)", "a*b*", "(a|b)*abb", "(ab|ac)+", "a?b?c?", "((a|b)c)*|c+", "(a(b|c)*)?a", "(((a|b)+c?(a|b)*)?(c(a|b)+|a?b?c+)((a|b|c)*)(a(a)+)?)+"] {
+            let arena = Corrida::new(None);
+            let nfa = parse_regex(regex, &arena).unwrap();
+            let from_nfa = nfa.to_regex().unwrap();
+            let from_dfa = nfa.as_dfa(&arena).to_regex().unwrap();
+
+            for converted in [&from_nfa, &from_dfa] {
+                let round_trip = parse_regex(converted, &arena).unwrap();
+                for word in &words {
+                    assert_eq!(round_trip.simulate_iter(word.chars()), nfa.simulate_iter(word.chars()), "{regex} became {converted}, which differs on {word:?}");
+                }
+            }
+        }
+    }
+}
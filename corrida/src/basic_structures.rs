@@ -23,7 +23,7 @@ pub mod binary_tree {
         }
 
         /// Returns an iterator that traverses the binary tree 'inorder'.
-        pub fn iter_in_order(&self) -> IterInOrder<T> {
+        pub fn iter_in_order(&self) -> IterInOrder<'_, T> {
             IterInOrder {
                 stack: vec![(self, false)]
             }
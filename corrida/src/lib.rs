@@ -2,7 +2,6 @@
 #![warn(missing_docs)]
 #![feature(allocator_api)]
 #![feature(slice_ptr_get)]
-#![feature(slice_from_ptr_range)]
 #![allow(incomplete_features)]
 #![feature(generic_const_exprs)]
 
@@ -219,7 +218,7 @@ mod test {
             for _ in 0..10_000 {
                 let _my_ref = arena.alloc(1);
             }
-            let big = arena.alloc([1; 10_000]);
+            let _big = arena.alloc([1; 10_000]);
         }
     }
 }
@@ -0,0 +1,126 @@
+#![warn(missing_docs)]
+
+//! Derive macros for gerber, re-exported by gerber behind the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+/// Derives `gerber::dfa::Indexable` for fieldless enums and for newtypes over an `Indexable` type, such as `u8`.
+///
+/// Enum variants are indexed in declaration order. Newtypes forward to the wrapped type.
+#[proc_macro_derive(Indexable)]
+pub fn derive_indexable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match indexable(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Derives `gerber::dfa::FromIndex` for fieldless enums and for newtypes over a `FromIndex` type, the inverse of `#[derive(Indexable)]`.
+#[proc_macro_derive(FromIndex)]
+pub fn derive_from_index(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match from_index(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn indexable(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (get_index, count) = match &input.data {
+        Data::Enum(data) => {
+            if let Some(variant) = data.variants.iter().find(|variant| !matches!(variant.fields, Fields::Unit)) {
+                return Err(syn::Error::new_spanned(variant, "Indexable can only be derived for enums without fields"));
+            }
+
+            let variants: Vec<_> = data.variants.iter().map(|variant| &variant.ident).collect();
+            let indices: Vec<usize> = (0..variants.len()).collect();
+            let count = variants.len();
+
+            (
+                quote! { match self { #(Self::#variants => #indices,)* } },
+                quote! { #count },
+            )
+        },
+        Data::Struct(data) if data.fields.len() == 1 => {
+            let field = data.fields.iter().next().unwrap();
+            let ty = &field.ty;
+            let access = match &field.ident {
+                Some(ident) => quote! { self.#ident },
+                None => quote! { self.0 },
+            };
+
+            (
+                quote! { <#ty as ::gerber::dfa::Indexable>::get_index(&#access) },
+                quote! { <#ty as ::gerber::dfa::Indexable>::count() },
+            )
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(input, "Indexable can only be derived for fieldless enums and single field structs"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::gerber::dfa::Indexable for #name #ty_generics #where_clause {
+            fn get_index(&self) -> usize {
+                #get_index
+            }
+
+            fn count() -> usize {
+                #count
+            }
+        }
+    })
+}
+
+fn from_index(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let from_index = match &input.data {
+        Data::Enum(data) => {
+            if let Some(variant) = data.variants.iter().find(|variant| !matches!(variant.fields, Fields::Unit)) {
+                return Err(syn::Error::new_spanned(variant, "FromIndex can only be derived for enums without fields"));
+            }
+
+            let variants: Vec<_> = data.variants.iter().map(|variant| &variant.ident).collect();
+            let indices: Vec<usize> = (0..variants.len()).collect();
+
+            quote! {
+                match index {
+                    #(#indices => Some(Self::#variants),)*
+                    _ => None,
+                }
+            }
+        },
+        Data::Struct(data) if data.fields.len() == 1 => {
+            let field = data.fields.iter().next().unwrap();
+            let ty = &field.ty;
+            let construct = match &field.ident {
+                Some(ident) => quote! { Self { #ident: value } },
+                None => quote! { Self(value) },
+            };
+
+            quote! {
+                <#ty as ::gerber::dfa::FromIndex>::from_index(index).map(|value| #construct)
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(input, "FromIndex can only be derived for fieldless enums and single field structs"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::gerber::dfa::FromIndex for #name #ty_generics #where_clause {
+            fn from_index(index: usize) -> Option<Self> {
+                #from_index
+            }
+        }
+    })
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter;
+use std::marker::PhantomData;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+use std::ptr::NonNull;
+
+use smallvec::Array;
+
+use crate::classes::{ByteClasses, Classes, SymbolClasses};
+use crate::graph;
+use crate::nfa::{Nfa, State};
+
+/// A fixed size set of NFA states, one bit per state.
+pub trait StateBits: Copy + Eq + Hash + BitOr<Output = Self> + BitAnd<Output = Self> + BitOrAssign {
+    /// The number of states the set can hold.
+    const CAPACITY: usize;
+    /// The empty set.
+    const EMPTY: Self;
+    /// Returns the set holding only the given state.
+    fn bit(index: usize) -> Self;
+    /// Returns the lowest state in the set, which must not be empty.
+    fn lowest(self) -> usize;
+    /// Returns the set without its lowest state.
+    fn without_lowest(self) -> Self;
+}
+
+macro_rules! impl_state_bits {
+    ($($int:ty),*) => {
+        $(
+            impl StateBits for $int {
+                const CAPACITY: usize = <$int>::BITS as usize;
+                const EMPTY: Self = 0;
+
+                fn bit(index: usize) -> Self {
+                    1 << index
+                }
+
+                fn lowest(self) -> usize {
+                    self.trailing_zeros() as usize
+                }
+
+                fn without_lowest(self) -> Self {
+                    self & (self - 1)
+                }
+            }
+        )*
+    };
+}
+
+impl_state_bits!(u64, u128);
+
+// MARK: BitNfa
+/// An NFA compiled for fast simulation, where every set of states is a single integer.
+///
+/// The targets of every state on every symbol class are precomputed together with their epsilon closure, so reading a symbol looks up its class once and ORs one mask per active state.
+pub struct BitNfa<Σ: Eq + Hash + Copy, B: StateBits = u64, C: Classes<Σ> = SymbolClasses<Σ>> {
+    start: B,
+    accept: B,
+    classes: C,
+    state_count: usize,
+    // The masks of every state on class c are stored at c * state_count.
+    masks: Vec<B>,
+    _boo: PhantomData<Σ>
+}
+
+/// Gives every distinct row of masks a class, numbered in order of first appearance, and lays the rows of the classes out one after the other.
+/// Returns the class of every row, together with the table of masks.
+fn classify<B: StateBits>(rows: impl Iterator<Item = Vec<B>>) -> (Vec<usize>, Vec<B>) {
+    let mut ids = HashMap::new();
+    let mut masks = Vec::new();
+    let classes = rows
+        .map(|row| {
+            let next = ids.len();
+            *ids.entry(row).or_insert_with_key(|row| {
+                masks.extend_from_slice(row);
+                next
+            })
+        })
+        .collect();
+    (classes, masks)
+}
+
+impl<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> Nfa<'a, State<TARGETS_HINT, Σ>>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    /// Compiles the NFA into a bit parallel NFA, returning None if it has more states than `B` can hold.
+    pub fn to_bit_nfa<B: StateBits>(&self) -> Option<BitNfa<Σ, B>> {
+        Self::bit_nfa(&self.states())
+    }
+
+    /// Compiles the given states, listed in the order of `states`, into a bit parallel NFA.
+    pub(crate) fn bit_nfa<B: StateBits>(states: &[&State<TARGETS_HINT, Σ>]) -> Option<BitNfa<Σ, B>> {
+        let (start, accept, rows) = Self::bit_rows(states)?;
+        let (symbols, rows): (Vec<Σ>, Vec<Vec<B>>) = rows.into_iter().unzip();
+
+        // Class 0 is the empty row, for the symbols without transitions.
+        let (classes, masks) = classify(iter::once(vec![B::EMPTY; states.len()]).chain(rows));
+        let count = masks.len() / states.len();
+
+        Some(BitNfa {
+            start,
+            accept,
+            classes: SymbolClasses::from_classes(symbols.into_iter().zip(classes.into_iter().skip(1)), count),
+            state_count: states.len(),
+            masks,
+            _boo: PhantomData
+        })
+    }
+
+    /// Returns the start set, the accept set and the targets of every state on every symbol with a transition, or None if the states do not fit in `B`.
+    fn bit_rows<B: StateBits>(states: &[&State<TARGETS_HINT, Σ>]) -> Option<(B, B, HashMap<Σ, Vec<B>>)> {
+        if states.len() > B::CAPACITY {
+            return None;
+        }
+
+        let ids = graph::state_ids(states);
+        let id = |state: &State<TARGETS_HINT, Σ>| ids[&(state as *const State<TARGETS_HINT, Σ>)];
+
+        let closures: Vec<B> = (0..states.len())
+            .map(|start| {
+                let mut closure = B::bit(start);
+                let mut stack = vec![start];
+                while let Some(cur) = stack.pop() {
+                    for next in states[cur].get_transitions(None).map(id) {
+                        if closure & B::bit(next) == B::EMPTY {
+                            closure |= B::bit(next);
+                            stack.push(next);
+                        }
+                    }
+                }
+                closure
+            })
+            .collect();
+
+        let mut rows: HashMap<Σ, Vec<B>> = HashMap::new();
+        let mut accept = B::EMPTY;
+        for (source, state) in states.iter().enumerate() {
+            if state.is_accept() {
+                accept |= B::bit(source);
+            }
+            for (symbol, target) in state.transitions() {
+                if let Some(symbol) = symbol {
+                    rows.entry(symbol).or_insert_with(|| vec![B::EMPTY; states.len()])[source] |= closures[id(target)];
+                }
+            }
+        }
+
+        Some((closures[0], accept, rows))
+    }
+
+    /// Compiles the NFA for simulation, using the smallest bit parallel NFA that fits, or the NFA itself if none does.
+    pub fn compile(&self) -> CompiledNfa<'a, TARGETS_HINT, Σ> {
+        match AnyBitNfa::new(&self.states()) {
+            Some(AnyBitNfa::Bits64(bits)) => CompiledNfa::Bits64(bits),
+            Some(AnyBitNfa::Bits128(bits)) => CompiledNfa::Bits128(bits),
+            None => CompiledNfa::Sets(Nfa::new(self.start_node)),
+        }
+    }
+}
+
+impl<'a, const TARGETS_HINT: usize> Nfa<'a, State<TARGETS_HINT, u8>>
+where
+    [NonNull<State<TARGETS_HINT, u8>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, u8>>>,
+{
+    /// Compiles the NFA into a bit parallel NFA like `to_bit_nfa`, looking the class of every byte up in a table instead of a hash map.
+    pub fn to_byte_bit_nfa<B: StateBits>(&self) -> Option<BitNfa<u8, B, ByteClasses>> {
+        let states = self.states();
+        let (start, accept, mut rows) = Self::bit_rows(&states)?;
+
+        let (classes, masks) = classify((0..=u8::MAX).map(|byte| rows.remove(&byte).unwrap_or_else(|| vec![B::EMPTY; states.len()])));
+        let mut table = [0; 256];
+        for (byte, class) in classes.into_iter().enumerate() {
+            table[byte] = class as u8;
+        }
+
+        Some(BitNfa {
+            start,
+            accept,
+            classes: ByteClasses::from_table(table, masks.len() / states.len()),
+            state_count: states.len(),
+            masks,
+            _boo: PhantomData
+        })
+    }
+}
+
+impl<Σ: Eq + Hash + Copy, B: StateBits, C: Classes<Σ>> BitNfa<Σ, B, C> {
+    /// Returns the states reached from the given set by reading the symbol, including their epsilon closure.
+    fn step(&self, mut set: B, symbol: Σ) -> B {
+        let row = self.classes.get_class(symbol) * self.state_count;
+        let masks = &self.masks[row..row + self.state_count];
+
+        let mut next = B::EMPTY;
+        while set != B::EMPTY {
+            next |= masks[set.lowest()];
+            set = set.without_lowest();
+        }
+        next
+    }
+
+    /// Simulates the NFA on the given input, returning if the NFA accepts the input.
+    pub fn simulate_iter(&self, input: impl Iterator<Item = Σ>) -> bool {
+        let mut set = self.start;
+        for symbol in input {
+            set = self.step(set, symbol);
+            if set == B::EMPTY {
+                return false;
+            }
+        }
+        set & self.accept != B::EMPTY
+    }
+
+    /// Simulates the NFA on the given input, returning if the NFA accepts the input.
+    pub fn simulate_slice(&self, input: &[Σ]) -> bool {
+        self.simulate_iter(input.iter().copied())
+    }
+}
+
+/// A bit parallel NFA in the smallest integer its states fit in, compiled by `Nfa` for its own simulation.
+pub(crate) enum AnyBitNfa<Σ: Eq + Hash + Copy> {
+    Bits64(BitNfa<Σ, u64>),
+    Bits128(BitNfa<Σ, u128>),
+}
+
+impl<Σ: Eq + Hash + Copy> AnyBitNfa<Σ> {
+    /// Compiles the given states, listed in the order of `Nfa::states`, returning None if there are more than 128.
+    pub(crate) fn new<const TARGETS_HINT: usize>(states: &[&State<TARGETS_HINT, Σ>]) -> Option<Self>
+    where
+        [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+    {
+        if let Some(bits) = Nfa::bit_nfa(states) {
+            Some(AnyBitNfa::Bits64(bits))
+        } else {
+            Nfa::bit_nfa(states).map(AnyBitNfa::Bits128)
+        }
+    }
+
+    /// Simulates the NFA on the given input, returning if the NFA accepts the input.
+    pub(crate) fn simulate_iter(&self, input: impl Iterator<Item = Σ>) -> bool {
+        match self {
+            AnyBitNfa::Bits64(bits) => bits.simulate_iter(input),
+            AnyBitNfa::Bits128(bits) => bits.simulate_iter(input),
+        }
+    }
+}
+
+// MARK: CompiledNfa
+/// An NFA compiled with `Nfa::compile`, simulated with bit sets when its states fit in 64 or 128 bits and with sets of states otherwise.
+pub enum CompiledNfa<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    /// An NFA with at most 64 states.
+    Bits64(BitNfa<Σ, u64>),
+    /// An NFA with at most 128 states.
+    Bits128(BitNfa<Σ, u128>),
+    /// An NFA too large for bit sets.
+    Sets(Nfa<'a, State<TARGETS_HINT, Σ>>),
+}
+
+impl<const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> CompiledNfa<'_, TARGETS_HINT, Σ>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    /// Simulates the NFA on the given input, returning if the NFA accepts the input.
+    pub fn simulate_iter(&self, input: impl Iterator<Item = Σ>) -> bool {
+        match self {
+            CompiledNfa::Bits64(bits) => bits.simulate_iter(input),
+            CompiledNfa::Bits128(bits) => bits.simulate_iter(input),
+            CompiledNfa::Sets(nfa) => nfa.simulate_sets(input),
+        }
+    }
+
+    /// Simulates the NFA on the given input, returning if the NFA accepts the input.
+    pub fn simulate_slice(&self, input: &[Σ]) -> bool {
+        self.simulate_iter(input.iter().copied())
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nfa_state_creator;
+    use corrida::Corrida;
+
+    /// Builds an NFA for words over {0, 1} whose n-th to last symbol is 1, with 2n states and an epsilon transition before every step.
+    fn nth_to_last(arena: &Corrida, n: usize) -> Nfa<'_, State<2, u8>> {
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+        let s_0 = new_state!(false, &[(Some(0), None), (Some(1), None)]);
+        let mut last = new_state!();
+        s_0.push_transition(Some(1), Some(last));
+        for _ in 1..n {
+            let next = new_state!();
+            let step = new_state!();
+            last.push_transition(Some(0), Some(step));
+            last.push_transition(Some(1), Some(step));
+            step.push_transition(None, Some(next));
+            last = next;
+        }
+        last.set_accept(true);
+        Nfa::new(s_0)
+    }
+
+    #[test]
+    fn test_bit_nfa() {
+        let arena = Corrida::new(None);
+        let nfa = nth_to_last(&arena, 4);
+        let bits = nfa.to_bit_nfa::<u64>().unwrap();
+        let bytes = nfa.to_byte_bit_nfa::<u64>().unwrap();
+        // 0 and 1 lead to different states, every other byte has no transition.
+        assert_eq!(bits.classes.count(), 3);
+        assert_eq!(bytes.classes.count(), 3);
+
+        for len in 0..10 {
+            for word in 0..(1u32 << len) {
+                let word: Vec<u8> = (0..len).map(|i| ((word >> i) & 1) as u8).collect();
+                let expected = nfa.simulate_sets(word.iter().copied());
+                assert_eq!(bits.simulate_slice(&word), expected);
+                assert_eq!(bytes.simulate_slice(&word), expected);
+            }
+        }
+        assert!(!bits.simulate_slice(&[1, 2, 0, 0, 0]));
+        assert!(!bytes.simulate_slice(&[1, 2, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_compile() {
+        let arena = Corrida::new(None);
+        let small = nth_to_last(&arena, 10);
+        let medium = nth_to_last(&arena, 50);
+        let large = nth_to_last(&arena, 100);
+
+        assert!(matches!(small.compile(), CompiledNfa::Bits64(_)));
+        assert!(medium.to_bit_nfa::<u64>().is_none());
+        assert!(matches!(medium.compile(), CompiledNfa::Bits128(_)));
+        assert!(matches!(large.compile(), CompiledNfa::Sets(_)));
+
+        for (nfa, n) in [(small, 10), (medium, 50), (large, 100)] {
+            let compiled = nfa.compile();
+            let mut word = vec![0, 1];
+            word.extend(vec![0; n - 1]);
+            assert!(compiled.simulate_slice(&word));
+            assert!(!compiled.simulate_slice(&word[1..n]));
+            word.push(1);
+            assert!(!compiled.simulate_slice(&word));
+        }
+    }
+
+    #[test]
+    fn test_automatic() {
+        let arena = Corrida::new(None);
+        let small = nth_to_last(&arena, 10);
+        let medium = nth_to_last(&arena, 50);
+        let large = nth_to_last(&arena, 100);
+
+        for (nfa, n) in [(&small, 10), (&medium, 50), (&large, 100)] {
+            let mut word = vec![0, 1];
+            word.extend(vec![0; n - 1]);
+            assert!(nfa.simulate_slice(&word));
+            assert!(!nfa.simulate_slice(&word[1..n]));
+        }
+
+        assert!(matches!(AnyBitNfa::new(&small.states()), Some(AnyBitNfa::Bits64(_))));
+        assert!(matches!(AnyBitNfa::new(&medium.states()), Some(AnyBitNfa::Bits128(_))));
+        assert!(AnyBitNfa::new(&large.states()).is_none());
+    }
+
+    #[test]
+    fn test_automatic_follows_changes() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        let s_0 = new_state!();
+        let s_1 = new_state!();
+        s_0.push_transition(Some('a'), Some(s_1));
+        let nfa = Nfa::new(s_0);
+        assert!(!nfa.simulate_slice(&['a']));
+
+        s_1.set_accept(true);
+        assert!(nfa.simulate_slice(&['a']));
+
+        let s_2 = new_state!(true);
+        s_1.push_transition(Some('b'), Some(s_2));
+        assert!(nfa.simulate_slice(&['a', 'b']));
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use corrida::Corrida;
+
+use crate::dfa::{Dfa, PartialState, State};
+
+/// Maps every symbol of an alphabet to the equivalence class it belongs to.
+/// Two symbols share a class if no transition of the DFA the classes were computed from can tell them apart.
+pub trait Classes<Σ> {
+    /// Returns the class of the symbol.
+    fn get_class(&self, symbol: Σ) -> usize;
+    /// Returns the number of classes.
+    fn count(&self) -> usize;
+}
+
+/// Splits the given symbols into classes, refining the partition once per state so that two symbols end up in the same class only if every state sends them to the same target.
+/// Class ids are assigned in order of first appearance in `symbols`.
+fn refine<Σ: Eq + Hash + Copy>(symbols: &[Σ], states: &[&PartialState<Σ>]) -> (Vec<usize>, usize) {
+    let mut classes = vec![0; symbols.len()];
+    let mut count = 1;
+
+    for state in states {
+        let mut split = HashMap::new();
+        for (i, &symbol) in symbols.iter().enumerate() {
+            let target = state.get_transition(symbol).map(|target| target as *const PartialState<Σ>);
+            let next = split.len();
+            classes[i] = *split.entry((classes[i], target)).or_insert(next);
+        }
+        count = split.len();
+    }
+
+    (classes, count)
+}
+
+// MARK: SymbolClasses
+/// Symbol classes for an arbitrary alphabet, such as `char`.
+/// Class 0 is reserved for every symbol that never appears on a transition, so the table only grows with the symbols the DFA actually uses.
+pub struct SymbolClasses<Σ: Eq + Hash + Copy> {
+    // Looked up once per symbol read, so this uses a faster hash than the standard map.
+    classes: hashbrown::HashMap<Σ, usize>,
+    count: usize,
+}
+
+impl<Σ: Eq + Hash + Copy> SymbolClasses<Σ> {
+    /// Creates classes from the class of every symbol with a transition, which must be between 1 and `count` exclusive.
+    pub(crate) fn from_classes(classes: impl IntoIterator<Item = (Σ, usize)>, count: usize) -> Self {
+        Self {
+            classes: classes.into_iter().collect(),
+            count,
+        }
+    }
+
+    /// Computes the coarsest symbol classes which the given DFA can not distinguish between.
+    pub fn from_dfa(dfa: &Dfa<'_, Σ, PartialState<Σ>>) -> Self {
+        let states = dfa.states();
+
+        let mut symbols = Vec::new();
+        let mut seen = HashSet::new();
+        for state in &states {
+            for (symbol, _) in state.transitions() {
+                if seen.insert(symbol) {
+                    symbols.push(symbol);
+                }
+            }
+        }
+
+        let (classes, count) = refine(&symbols, &states);
+
+        Self {
+            classes: symbols.into_iter().zip(classes.into_iter().map(|class| class + 1)).collect(),
+            count: if seen.is_empty() { 1 } else { count + 1 },
+        }
+    }
+}
+
+impl<Σ: Eq + Hash + Copy> Classes<Σ> for SymbolClasses<Σ> {
+    fn get_class(&self, symbol: Σ) -> usize {
+        self.classes.get(&symbol).copied().unwrap_or(0)
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+}
+
+// MARK: ByteClasses
+/// Symbol classes for the byte alphabet, stored as a lookup table over all 256 bytes.
+pub struct ByteClasses {
+    table: [u8; 256],
+    count: usize,
+}
+
+impl ByteClasses {
+    /// Creates classes from the class of every byte, which must be below `count`.
+    pub(crate) fn from_table(table: [u8; 256], count: usize) -> Self {
+        Self {
+            table,
+            count
+        }
+    }
+
+    /// Computes the coarsest byte classes which the given DFA can not distinguish between.
+    pub fn from_dfa(dfa: &Dfa<'_, u8, PartialState<u8>>) -> Self {
+        let states = dfa.states();
+        let symbols: Vec<u8> = (0..=u8::MAX).collect();
+
+        let (classes, count) = refine(&symbols, &states);
+
+        let mut table = [0; 256];
+        for (byte, class) in classes.into_iter().enumerate() {
+            table[byte] = class as u8;
+        }
+
+        Self {
+            table,
+            count
+        }
+    }
+}
+
+impl Classes<u8> for ByteClasses {
+    fn get_class(&self, symbol: u8) -> usize {
+        self.table[symbol as usize] as usize
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+}
+
+// MARK: ClassState
+/// A node in a class indexed DFA. Every node has exactly one transition per symbol class, missing transitions of the source DFA are routed to a dead state.
+pub struct ClassState {
+    transitions: Box<[NonNull<ClassState>]>,
+    is_accept: bool,
+}
+
+impl ClassState {
+    /// Returns the next state for the given class.
+    pub fn get_transition(&self, class: usize) -> &ClassState {
+        // Safety, every transition points to a ClassState allocated in the same arena as this one.
+        unsafe { &*self.transitions[class].as_ptr() }
+    }
+
+    /// Returns the accept state flag.
+    pub fn is_accept(&self) -> bool {
+        self.is_accept
+    }
+}
+
+// MARK: ClassDfa
+/// A complete DFA whose transition tables are indexed by symbol class rather than by symbol.
+/// Every state only stores one transition per class, which keeps complete DFAs over large alphabets like `u8` or `char` compact.
+pub struct ClassDfa<'a, Σ, C: Classes<Σ>> {
+    start_node: &'a ClassState,
+    classes: C,
+    _boo: PhantomData<Σ>
+}
+
+impl<'a, Σ: Eq + Hash + Copy, C: Classes<Σ>> ClassDfa<'a, Σ, C> {
+    /// Builds a class indexed copy of the given DFA in the arena. The classes must have been computed from the same DFA.
+    pub fn new(dfa: &Dfa<'_, Σ, PartialState<Σ>>, classes: C, arena: &'a Corrida) -> Self {
+        let states = dfa.states();
+        let count = classes.count();
+
+        let index: HashMap<*const PartialState<Σ>, usize> = states.iter()
+            .enumerate()
+            .map(|(i, &state)| (state as *const PartialState<Σ>, i))
+            .collect();
+
+        let class_states: Vec<NonNull<ClassState>> = states.iter().map(|state| {
+            NonNull::from(arena.alloc(ClassState {
+                transitions: Box::new([]),
+                is_accept: state.is_accept(),
+            }))
+        }).collect();
+
+        let mut dead = None;
+        for (state, &class_state) in states.iter().zip(&class_states) {
+            let mut row: Vec<Option<NonNull<ClassState>>> = vec![None; count];
+            for (symbol, target) in state.transitions() {
+                row[classes.get_class(symbol)] = Some(class_states[index[&(target as *const PartialState<Σ>)]]);
+            }
+
+            let row = row.into_iter().map(|target| target.unwrap_or_else(|| *dead.get_or_insert_with(|| {
+                let dead_state = arena.alloc(ClassState {
+                    transitions: Box::new([]),
+                    is_accept: false,
+                });
+                let dead_ptr = NonNull::from(&mut *dead_state);
+                dead_state.transitions = vec![dead_ptr; count].into_boxed_slice();
+                dead_ptr
+            }))).collect();
+
+            // Safety, every ClassState was just allocated in the arena and no other references to it exist yet.
+            unsafe { (*class_state.as_ptr()).transitions = row; }
+        }
+
+        Self {
+            // Safety, the start node is the first state in breadth first order, and lives as long as the arena.
+            start_node: unsafe { &*class_states[0].as_ptr() },
+            classes,
+            _boo: PhantomData
+        }
+    }
+
+    /// Returns the symbol classes used to index the transition tables.
+    pub fn classes(&self) -> &C {
+        &self.classes
+    }
+
+    /// Tests the provided input sequence on an iterator, returning true if the DFA ends at an accept state.
+    pub fn simulate_iter(&self, input: impl Iterator<Item = Σ>) -> bool {
+        let mut cur = self.start_node;
+        for symbol in input {
+            cur = cur.get_transition(self.classes.get_class(symbol));
+        }
+        cur.is_accept()
+    }
+
+    /// Tests the provided input sequence, returning true if the DFA ends at an accept state.
+    pub fn simulate_slice(&self, input: &[Σ]) -> bool {
+        self.simulate_iter(input.iter().copied())
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dfa_state_creator;
+
+    #[test]
+    fn test_byte_classes() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<u8>);
+
+        // Accepts identifiers made of lowercase letters followed by digits.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true);
+            let s_2 = new_state!(true);
+            for byte in b'a'..=b'z' {
+                s_0.add_transition((byte, Some(s_1)));
+                s_1.add_transition((byte, None));
+            }
+            for byte in b'0'..=b'9' {
+                s_1.add_transition((byte, Some(s_2)));
+                s_2.add_transition((byte, None));
+            }
+            s_0
+        };
+
+        let dfa = Dfa::<u8, PartialState<u8>>::new(start_node);
+        let classes = ByteClasses::from_dfa(&dfa);
+
+        assert_eq!(classes.count(), 3);
+        assert_eq!(classes.get_class(b'a'), classes.get_class(b'q'));
+        assert_eq!(classes.get_class(b'0'), classes.get_class(b'9'));
+        assert_eq!(classes.get_class(b'!'), classes.get_class(b'A'));
+        assert_ne!(classes.get_class(b'a'), classes.get_class(b'0'));
+        assert_ne!(classes.get_class(b'a'), classes.get_class(b'A'));
+
+        let class_dfa = ClassDfa::new(&dfa, classes, &arena);
+        for input in [&b"abc"[..], b"abc123", b"", b"1abc", b"ab1c", b"z9", b"a!"] {
+            assert_eq!(class_dfa.simulate_slice(input), dfa.simulate_slice(input));
+        }
+    }
+
+    #[test]
+    fn test_symbol_classes() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<char>);
+
+        // Accepts an even number of vowels, ignoring 'x' and 'y'.
+        let start_node = {
+            let s_0 = new_state!(true);
+            let s_1 = new_state!();
+            for vowel in ['a', 'e', 'i', 'o', 'u'] {
+                s_0.add_transition((vowel, Some(s_1)));
+                s_1.add_transition((vowel, Some(s_0)));
+            }
+            for other in ['x', 'y'] {
+                s_0.add_transition((other, None));
+                s_1.add_transition((other, None));
+            }
+            s_0
+        };
+
+        let dfa = Dfa::<char, PartialState<char>>::new(start_node);
+        let classes = SymbolClasses::from_dfa(&dfa);
+
+        assert_eq!(classes.count(), 3);
+        assert_eq!(classes.get_class('€'), 0);
+        assert_eq!(classes.get_class('a'), classes.get_class('u'));
+        assert_eq!(classes.get_class('x'), classes.get_class('y'));
+
+        let class_dfa = ClassDfa::new(&dfa, classes, &arena);
+        for input in ["", "ae", "axe", "xyz", "aeiou", "aeioux", "ééa"] {
+            let input: Vec<char> = input.chars().collect();
+            assert_eq!(class_dfa.simulate_slice(&input), dfa.simulate_slice(&input));
+        }
+    }
+}
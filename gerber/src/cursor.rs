@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::dfa::{Dfa, State};
+
+// MARK: DfaCursor
+/// A resumable position in a DFA. Input can be fed in any number of chunks, and the cursor can be cloned to fork matching at the current position.
+pub struct DfaCursor<'a, Σ: Eq + Hash + Copy, S: State<Σ>> {
+    start_node: &'a S,
+    current: Option<&'a S>,
+    live: Rc<HashSet<*const S>>,
+    consumed: usize,
+    _boo: PhantomData<Σ>
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Returns a cursor positioned at the start node of the DFA.
+    pub fn cursor(&self) -> DfaCursor<'a, Σ, S> {
+        let mut cursor = DfaCursor {
+            start_node: self.start_node,
+            current: None,
+            live: Rc::new(self.live_states()),
+            consumed: 0,
+            _boo: PhantomData
+        };
+        cursor.reset();
+        cursor
+    }
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> DfaCursor<'a, Σ, S> {
+    /// Feeds the next chunk of input, returning false once the cursor is dead.
+    /// Stops reading as soon as a dead state is reached, since no further input can lead to an accept state.
+    pub fn feed(&mut self, chunk: &[Σ]) -> bool {
+        self.feed_iter(chunk.iter().copied())
+    }
+
+    /// Feeds the next chunk of input from an iterator, returning false once the cursor is dead.
+    pub fn feed_iter(&mut self, chunk: impl Iterator<Item = Σ>) -> bool {
+        for symbol in chunk {
+            let Some(cur) = self.current else {
+                return false;
+            };
+
+            self.consumed += 1;
+            self.current = cur.step(symbol).filter(|&next| self.is_live(next));
+        }
+        self.current.is_some()
+    }
+
+    fn is_live(&self, state: &S) -> bool {
+        self.live.contains(&(state as *const S))
+    }
+
+    /// Returns true if the input fed so far is accepted by the DFA.
+    pub fn is_accept(&self) -> bool {
+        self.current.is_some_and(|cur| cur.is_accept())
+    }
+
+    /// Returns true if no continuation of the input fed so far can be accepted.
+    pub fn is_dead(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Returns the number of symbols read, symbols after the cursor died are not counted.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Moves the cursor back to the start node, forgetting all input fed so far.
+    pub fn reset(&mut self) {
+        self.current = Some(self.start_node).filter(|&start| self.is_live(start));
+        self.consumed = 0;
+    }
+}
+
+impl<Σ: Eq + Hash + Copy, S: State<Σ>> Clone for DfaCursor<'_, Σ, S> {
+    fn clone(&self) -> Self {
+        Self {
+            start_node: self.start_node,
+            current: self.current,
+            live: self.live.clone(),
+            consumed: self.consumed,
+            _boo: PhantomData
+        }
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dfa::PartialState;
+    use crate::dfa_state_creator;
+    use corrida::Corrida;
+
+    #[test]
+    fn test_chunks() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<u8>);
+
+        // Accepts "GET " followed by any number of lowercase letters.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!();
+            let s_3 = new_state!();
+            let s_4 = new_state!(true);
+            s_0.add_transition((b'G', Some(s_1)));
+            s_1.add_transition((b'E', Some(s_2)));
+            s_2.add_transition((b'T', Some(s_3)));
+            s_3.add_transition((b' ', Some(s_4)));
+            for byte in b'a'..=b'z' {
+                s_4.add_transition((byte, None));
+            }
+            s_0
+        };
+
+        let dfa = Dfa::<u8, PartialState<u8>>::new(start_node);
+        let mut cursor = dfa.cursor();
+
+        assert!(cursor.feed(b"GE"));
+        assert!(!cursor.is_accept());
+        assert!(cursor.feed(b"T i"));
+        assert!(cursor.is_accept());
+
+        let mut fork = cursor.clone();
+        assert!(!fork.feed(b"ndex!html"));
+        assert!(fork.is_dead());
+        assert_eq!(fork.consumed(), 10);
+
+        assert!(cursor.feed(b"ndex"));
+        assert!(cursor.is_accept());
+        assert_eq!(cursor.consumed(), 9);
+
+        cursor.reset();
+        assert!(!cursor.feed(b"POST"));
+        assert!(cursor.is_dead());
+        assert_eq!(cursor.consumed(), 1);
+    }
+
+    #[test]
+    fn test_dead_sink() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<bool>);
+
+        // Accepts true*, false moves into a sink which loops on everything.
+        let start_node = {
+            let s_0 = new_state!(true, &[(true, None)]);
+            let sink = new_state!(false, &[(true, None), (false, None)]);
+            s_0.add_transition((false, Some(sink)));
+            s_0
+        };
+
+        let dfa = Dfa::<bool, PartialState<bool>>::new(start_node).to_complete(&arena);
+        let mut cursor = dfa.cursor();
+        assert!(cursor.feed(&[true, true]));
+        assert!(!cursor.feed(&[false, true, true]));
+        assert!(cursor.is_dead());
+        assert_eq!(cursor.consumed(), 3);
+    }
+}
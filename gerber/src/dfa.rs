@@ -1,8 +1,17 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
 
+use corrida::Corrida;
 use smallmap::Map;
+use smallvec::{Array, SmallVec};
+
+use crate::graph;
+use crate::nfa::{Nfa, State as NfaState};
+use crate::PatternId;
 
 /// A node in the DFA, contains is_accept and a transition hashmap. MARK: State
 pub trait State<Σ:Eq + Hash + Copy> {
@@ -13,16 +22,52 @@ pub trait State<Σ:Eq + Hash + Copy> {
     fn get_transition(&self, symbol: Self::Index) -> Option<&Self>;
     /// Adds a transition to the hashmap. None represents a self-transition.
     fn add_transition(&mut self, transition: (Σ, Option<&Self>));
-    /// Sets the accept state flag.
+    /// Sets the accept state flag. Accepting states without patterns report pattern 0.
     fn set_accept(&mut self, accept: bool);
     /// Returns the accept state flag.
     fn is_accept(&self) -> bool;
+    /// Returns the sorted ids of the patterns matched in this state, empty if the state does not accept.
+    /// By default an accepting state matches pattern 0 only.
+    fn patterns(&self) -> &[PatternId] {
+        if self.is_accept() { &[0] } else { &[] }
+    }
+    /// Sets the patterns matched in this state, the state accepts if any are given.
+    /// By default only the accept flag is kept, states which store pattern ids override this.
+    fn set_patterns(&mut self, patterns: &[PatternId]) {
+        self.set_accept(!patterns.is_empty());
+    }
+    /// Returns an iterator over the outgoing transitions of this state.
+    fn transitions(&self) -> impl Iterator<Item = (Σ, &Self)>;
+    /// Returns the next state after reading the symbol, if the transition exists.
+    fn step(&self, symbol: Σ) -> Option<&Self>;
+    /// Called once subset construction has built every state of a DFA, with all of them. Complete states route their missing transitions to a dead state here.
+    fn finish_construction(_states: &[NonNull<Self>], _arena: &Corrida) where Self: Sized {}
 }
 
 /// A node in the DFA, this version uses a hashmap is intended to be used when constructing a partial DFA.
 pub struct PartialState<Σ: Eq + Hash + Copy> {
     transitions: Map<Σ, NonNull<PartialState<Σ>>>,
-    is_accept: bool
+    patterns: Patterns
+}
+
+/// The patterns matched in a DFA state, most states match at most one or two.
+type Patterns = SmallVec<[PatternId; 2]>;
+
+/// Sets the accept flag of a pattern list, keeping existing patterns when already accepting.
+fn set_accept_patterns(patterns: &mut Patterns, accept: bool) {
+    if !accept {
+        patterns.clear();
+    } else if patterns.is_empty() {
+        patterns.push(0);
+    }
+}
+
+/// Replaces a pattern list, keeping it sorted and free of duplicates.
+fn set_patterns(patterns: &mut Patterns, new: &[PatternId]) {
+    patterns.clear();
+    patterns.extend_from_slice(new);
+    patterns.sort_unstable();
+    patterns.dedup();
 }
 
 
@@ -31,11 +76,17 @@ impl<Σ:Eq + Hash + Copy> PartialState<Σ> {
     pub fn new() -> Self {
         Self {
             transitions: Map::new(),
-            is_accept: false
+            patterns: SmallVec::new()
         }
     }
 }
 
+impl<Σ:Eq + Hash + Copy> Default for PartialState<Σ> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 
 impl<Σ:Eq + Hash + Copy> State<Σ> for PartialState<Σ> 
 {
@@ -55,11 +106,30 @@ impl<Σ:Eq + Hash + Copy> State<Σ> for PartialState<Σ>
     }
 
     fn set_accept(&mut self, accept: bool) {
-        self.is_accept = accept;
+        set_accept_patterns(&mut self.patterns, accept);
     }
 
     fn is_accept(&self) -> bool {
-        self.is_accept
+        !self.patterns.is_empty()
+    }
+
+    fn patterns(&self) -> &[PatternId] {
+        &self.patterns
+    }
+
+    fn set_patterns(&mut self, patterns: &[PatternId]) {
+        set_patterns(&mut self.patterns, patterns);
+    }
+
+    fn transitions(&self) -> impl Iterator<Item = (Σ, &PartialState<Σ>)> {
+        self.transitions.iter().map(|(symbol, target)| {
+            // Safety, ptr dereference is coming directly from a reference to a PartialState<Σ>
+            (*symbol, unsafe { &*target.as_ptr() })
+        })
+    }
+
+    fn step(&self, symbol: Σ) -> Option<&PartialState<Σ>> {
+        self.get_transition(symbol)
     }
 }
 
@@ -71,10 +141,70 @@ pub trait Indexable {
     fn count() -> usize;
 }
 
+/// Indexable symbols which can be rebuilt from their index, needed to list the transitions of a `CompleteState`.
+pub trait FromIndex: Indexable + Sized {
+    /// Returns the symbol with the given index, the inverse of `get_index`.
+    ///
+    /// Must return Some for every index below `count`, and None for every index at or above it.
+    fn from_index(index: usize) -> Option<Self>;
+}
+
+/// Derives `Indexable` for fieldless enums and newtypes over an `Indexable` type.
+#[cfg(feature = "derive")]
+pub use gerber_derive::Indexable;
+
+/// Derives `FromIndex` for fieldless enums and newtypes over a `FromIndex` type.
+#[cfg(feature = "derive")]
+pub use gerber_derive::FromIndex;
+
+impl Indexable for bool {
+    fn get_index(&self) -> usize {
+        *self as usize
+    }
+
+    fn count() -> usize {
+        2
+    }
+}
+
+impl FromIndex for bool {
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_indexable_int {
+    ($($int: ty),*) => {
+        $(
+            impl Indexable for $int {
+                fn get_index(&self) -> usize {
+                    *self as usize
+                }
+
+                fn count() -> usize {
+                    <$int>::MAX as usize + 1
+                }
+            }
+
+            impl FromIndex for $int {
+                fn from_index(index: usize) -> Option<Self> {
+                    <$int>::try_from(index).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_indexable_int!(u8, u16);
+
 /// A node in the DFA, this version uses a vector and is intended to be used when constructing a complete DFA.
 pub struct CompleteState<Σ: Eq + Hash + Copy + Indexable> {
     transitions: Vec<Option<NonNull<CompleteState<Σ>>>>,
-    is_accept: bool,
+    patterns: Patterns,
     _boo: PhantomData<Σ>
 }
 
@@ -83,13 +213,38 @@ impl<Σ:Eq + Hash + Copy + Indexable> CompleteState<Σ> {
     pub fn new() -> Self {
         Self {
             transitions: vec![None; Σ::count()],
-            is_accept: false,
+            patterns: SmallVec::new(),
             _boo: PhantomData
         }
     }
 }
 
-impl<Σ:Eq + Hash + Copy + Indexable> State<Σ> for CompleteState<Σ> {
+impl<Σ:Eq + Hash + Copy + Indexable> CompleteState<Σ> {
+    /// Routes every missing transition of the given states to a single non accepting dead state, allocated only if needed.
+    fn fill_with_dead_state(states: &[NonNull<CompleteState<Σ>>], arena: &Corrida) {
+        let mut dead = None;
+        for state in states {
+            // Safety, the states were just allocated in the arena by the caller and are only reachable through these pointers.
+            let state = unsafe { &mut *state.as_ptr() };
+            for target in state.transitions.iter_mut().filter(|target| target.is_none()) {
+                *target = Some(*dead.get_or_insert_with(|| {
+                    let dead_state = arena.alloc(CompleteState::new());
+                    let dead_ptr = NonNull::from(&mut *dead_state);
+                    dead_state.transitions.fill(Some(dead_ptr));
+                    dead_ptr
+                }));
+            }
+        }
+    }
+}
+
+impl<Σ:Eq + Hash + Copy + Indexable> Default for CompleteState<Σ> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Σ:Eq + Hash + Copy + FromIndex> State<Σ> for CompleteState<Σ> {
     type Index = usize;
 
     fn get_transition(&self, index: Self::Index) -> Option<&CompleteState<Σ>> {
@@ -106,11 +261,34 @@ impl<Σ:Eq + Hash + Copy + Indexable> State<Σ> for CompleteState<Σ> {
     }
 
     fn set_accept(&mut self, accept: bool) {
-        self.is_accept = accept;
+        set_accept_patterns(&mut self.patterns, accept);
     }
 
     fn is_accept(&self) -> bool {
-        self.is_accept
+        !self.patterns.is_empty()
+    }
+
+    fn patterns(&self) -> &[PatternId] {
+        &self.patterns
+    }
+
+    fn set_patterns(&mut self, patterns: &[PatternId]) {
+        set_patterns(&mut self.patterns, patterns);
+    }
+
+    fn transitions(&self) -> impl Iterator<Item = (Σ, &CompleteState<Σ>)> {
+        self.transitions.iter().enumerate().filter_map(|(index, target)| {
+            // Safety, ptr dereference is coming directly from a reference to a CompleteState<Σ>
+            Some((Σ::from_index(index)?, unsafe { &*target.as_ref()?.as_ptr() }))
+        })
+    }
+
+    fn step(&self, symbol: Σ) -> Option<&CompleteState<Σ>> {
+        self.get_transition(symbol.get_index())
+    }
+
+    fn finish_construction(states: &[NonNull<CompleteState<Σ>>], arena: &Corrida) {
+        Self::fill_with_dead_state(states, arena);
     }
 }
 
@@ -120,10 +298,133 @@ impl<Σ:Eq + Hash + Copy + Indexable> State<Σ> for CompleteState<Σ> {
 /// Symbol type Σ must be hashable and implement display (not asking for alot here..)
 /// 
 pub struct Dfa<'a, Σ: Eq + Hash + Copy, S: State<Σ>> {
-    start_node: &'a S,
+    pub(crate) start_node: &'a S,
     _boo: PhantomData<Σ>
 }
 
+impl<'a, Σ:Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Creates a DFA for any state type from its start node.
+    pub(crate) fn from_start(start_node: &'a S) -> Self {
+        Self {
+            start_node,
+            _boo: PhantomData
+        }
+    }
+
+    /// Returns the patterns matched by the whole input, empty if the input is rejected.
+    pub fn matching_patterns(&self, input: &[Σ]) -> &'a [PatternId] {
+        let mut cur = self.start_node;
+        for &symbol in input {
+            match cur.step(symbol) {
+                Some(next) => cur = next,
+                None => return &[],
+            }
+        }
+        cur.patterns()
+    }
+
+    /// Returns every state reachable from the start node, in breadth first order starting with the start node.
+    /// The position of a state in the list is its id, the numbering used by traces, serialization and every other report on states.
+    pub fn states(&self) -> Vec<&'a S> {
+        graph::reachable(self.start_node, |state| state.transitions().map(|(_, next)| next))
+    }
+
+    /// Returns the position of every reachable state in breadth first order, the numbering used when reporting states.
+    pub(crate) fn state_ids(&self) -> HashMap<*const S, usize> {
+        graph::state_ids(&self.states())
+    }
+
+    /// Returns the number of reachable states.
+    pub fn state_count(&self) -> usize {
+        self.states().len()
+    }
+
+    /// Returns the number of transitions between reachable states.
+    pub fn transition_count(&self) -> usize {
+        self.states().iter().map(|state| state.transitions().count()).sum()
+    }
+
+    /// Returns every symbol with a transition from a reachable state, in the order they are first seen walking the states by id.
+    pub fn alphabet(&self) -> Vec<Σ> {
+        let mut seen = HashSet::new();
+        self.states().iter()
+            .flat_map(|state| state.transitions().map(|(symbol, _)| symbol))
+            .filter(|&symbol| seen.insert(symbol))
+            .collect()
+    }
+
+    /// Returns the ids of the accept states, in increasing order.
+    pub fn accept_states(&self) -> Vec<usize> {
+        self.states().iter().enumerate().filter(|(_, state)| state.is_accept()).map(|(id, _)| id).collect()
+    }
+
+    /// Returns every reachable state from which an accept state can be reached.
+    pub(crate) fn live_states(&self) -> HashSet<*const S> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let targets: Vec<Vec<usize>> = states.iter()
+            .map(|state| state.transitions().map(|(_, next)| ids[&(next as *const S)]).collect())
+            .collect();
+        let accepting: Vec<bool> = states.iter().map(|state| state.is_accept()).collect();
+
+        graph::co_reachable(&targets, &accepting).into_iter()
+            .zip(states)
+            .filter(|&(live, _)| live)
+            .map(|(_, state)| state as *const S)
+            .collect()
+    }
+
+    /// Builds an NFA for the reverse language in the arena, accepting `w` exactly when this DFA accepts `w` reversed.
+    /// Reversing a DFA generally introduces non-determinism, so the result is an NFA which can be converted back with `Nfa::as_dfa`.
+    pub fn reverse<'b, const TARGETS_HINT: usize>(&self, arena: &'b Corrida) -> Nfa<'b, NfaState<TARGETS_HINT, Σ>>
+    where
+        [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+    {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let reversed: Vec<NonNull<NfaState<TARGETS_HINT, Σ>>> = states.iter()
+            .map(|&state| NonNull::from(arena.alloc(NfaState::new(std::ptr::eq(state, self.start_node)))))
+            .collect();
+
+        let edges = states.iter().enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, target)| (target, symbol, id)))
+            .map(|(target, symbol, id)| (ids[&(target as *const S)], Some(symbol), id));
+        // Safety, the reversed states were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&reversed, edges, |state, symbol, target| state.push_transition(symbol, target)) };
+
+        let start = arena.alloc(NfaState::new(false));
+        for (state, reversed) in states.iter().zip(&reversed) {
+            if state.is_accept() {
+                start.push_transition(None, Some(unsafe { reversed.as_ref() }));
+            }
+        }
+
+        Nfa::new(start)
+    }
+
+    /// Copies the given states into new states of type `T` in the arena, keeping accept flags and every transition between copied states.
+    /// Transitions into states which were not copied are dropped.
+    pub(crate) fn copy_states<T: State<Σ> + Default>(states: &[&S], arena: &Corrida) -> Vec<NonNull<T>> {
+        let ids = graph::state_ids(states);
+
+        let copies: Vec<NonNull<T>> = states.iter().map(|state| {
+            let copy = arena.alloc(T::default());
+            copy.set_patterns(state.patterns());
+            NonNull::from(copy)
+        }).collect();
+
+        let edges = states.iter().enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, next)| (id, symbol, next)))
+            .filter_map(|(id, symbol, next)| Some((id, symbol, *ids.get(&(next as *const S))?)));
+        // Safety, every copy was just allocated in the arena and is only reachable through these pointers.
+        unsafe { graph::link(&copies, edges, |copy, symbol, target| copy.add_transition((symbol, target))) };
+
+        copies
+    }
+}
+
 impl<'a, Σ:Eq + Hash + Copy> Dfa<'a, Σ, PartialState<Σ>>{
     /// Creates a new DFA with no vertices, but an Arena ready for pushing verts.
     pub fn new(start_node: &'a PartialState<Σ>) -> Self {
@@ -153,7 +454,19 @@ impl<'a, Σ:Eq + Hash + Copy> Dfa<'a, Σ, PartialState<Σ>>{
     }
 }
 
-impl<'a, Σ:Eq + Hash + Copy + Indexable> Dfa<'a, Σ, CompleteState<Σ>>{
+impl<'a, Σ:Eq + Hash + Copy + FromIndex, S: State<Σ>> Dfa<'a, Σ, S>{
+    /// Copies the DFA into the arena as a complete DFA, routing every missing transition to a single non accepting dead state.
+    /// The dead state is only added if the DFA has missing transitions.
+    pub fn to_complete<'b>(&self, arena: &'b Corrida) -> Dfa<'b, Σ, CompleteState<Σ>> {
+        let copies = Self::copy_states(&self.states(), arena);
+        CompleteState::fill_with_dead_state(&copies, arena);
+
+        // Safety, the start node is the first state in breadth first order, and lives as long as the arena.
+        Dfa::<Σ, CompleteState<Σ>>::new(unsafe { &*copies[0].as_ptr() })
+    }
+}
+
+impl<'a, Σ:Eq + Hash + Copy + FromIndex> Dfa<'a, Σ, CompleteState<Σ>>{
     /// Creates a new DFA with no vertices, but an Arena ready for pushing verts.
     pub fn new(start_node: &'a CompleteState<Σ>) -> Self {
         Self {
@@ -180,8 +493,80 @@ impl<'a, Σ:Eq + Hash + Copy + Indexable> Dfa<'a, Σ, CompleteState<Σ>>{
     pub fn simulate_slice(&self, input: &[Σ]) -> bool {
         self.simulate_iter(input.iter().copied())
     }
+
+    /// Tests the provided input sequence on an iterator like `simulate_iter`, but returns an error instead of panicking when a transition is missing.
+    pub fn try_simulate_iter(&self, input: impl Iterator<Item = Σ>) -> Result<bool, MissingTransition<Σ>> {
+        let mut cur: &CompleteState<Σ> = self.start_node;
+        for (position, symbol) in input.enumerate() {
+            match cur.get_transition(symbol.get_index()) {
+                Some(next) => cur = next,
+                None => {
+                    return Err(MissingTransition {
+                        state: self.state_ids()[&(cur as *const CompleteState<Σ>)],
+                        symbol,
+                        position
+                    });
+                }
+            }
+        }
+        Ok(cur.is_accept())
+    }
+
+    /// Tests the provided input sequence like `simulate_slice`, but returns an error instead of panicking when a transition is missing.
+    pub fn try_simulate(&self, input: &[Σ]) -> Result<bool, MissingTransition<Σ>> {
+        self.try_simulate_iter(input.iter().copied())
+    }
+
+    /// Returns every missing transition as a list of (state, symbol) pairs. States are numbered in breadth first order from the start node, which is state 0.
+    pub fn validate(&self) -> Vec<(usize, Σ)> {
+        let mut missing = Vec::new();
+        for (id, state) in self.states().into_iter().enumerate() {
+            let symbols = (0..Σ::count()).map_while(Σ::from_index);
+            missing.extend(symbols.zip(&state.transitions).filter(|(_, target)| target.is_none()).map(|(symbol, _)| (id, symbol)));
+        }
+        missing
+    }
+
+    /// Copies the DFA into the arena, routing every missing transition to a single non accepting dead state, the same as `to_complete`.
+    pub fn complete_with_dead_state<'b>(&self, arena: &'b Corrida) -> Dfa<'b, Σ, CompleteState<Σ>> {
+        self.to_complete(arena)
+    }
+
+    /// Copies the DFA into the arena as a partial DFA, dropping every dead state (states from which no accept state can be reached) and the transitions into them.
+    pub fn to_partial<'b>(&self, arena: &'b Corrida) -> Dfa<'b, Σ, PartialState<Σ>> {
+        let live = self.live_states();
+        let states: Vec<_> = self.states().into_iter()
+            .enumerate()
+            .filter(|&(id, state)| id == 0 || live.contains(&(state as *const CompleteState<Σ>)))
+            .map(|(_, state)| state)
+            .collect();
+
+        let copies = Self::copy_states(&states, arena);
+
+        // Safety, the start node is always copied first, and lives as long as the arena.
+        Dfa::<Σ, PartialState<Σ>>::new(unsafe { &*copies[0].as_ptr() })
+    }
+}
+
+/// A transition which was needed while simulating a complete DFA, but was never provided.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissingTransition<Σ> {
+    /// The state the transition is missing from, numbered in breadth first order from the start node.
+    pub state: usize,
+    /// The symbol with no transition.
+    pub symbol: Σ,
+    /// The position in the input at which the symbol was read.
+    pub position: usize,
+}
+
+impl<Σ: Debug> Display for MissingTransition<Σ> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "no transition from state {} on symbol {:?} at position {}", self.state, self.symbol, self.position)
+    }
 }
 
+impl<Σ: Debug> Error for MissingTransition<Σ> {}
+
 /// A macro for which allows you to make a state creator function for a given state type.
 #[macro_export]
 macro_rules! dfa_state_creator {
@@ -211,6 +596,33 @@ mod test {
     use super::*;
     use corrida::Corrida;
 
+    /// A state with only an accept flag, relying on the default pattern methods.
+    struct FlagState(bool);
+
+    impl State<u8> for FlagState {
+        type Index = u8;
+
+        fn get_transition(&self, _symbol: u8) -> Option<&Self> { None }
+        fn add_transition(&mut self, _transition: (u8, Option<&Self>)) {}
+        fn set_accept(&mut self, accept: bool) { self.0 = accept; }
+        fn is_accept(&self) -> bool { self.0 }
+        fn transitions(&self) -> impl Iterator<Item = (u8, &Self)> { std::iter::empty() }
+        fn step(&self, _symbol: u8) -> Option<&Self> { None }
+    }
+
+    #[test]
+    pub fn test_default_patterns() {
+        let mut state = FlagState(false);
+        assert!(state.patterns().is_empty());
+
+        state.set_patterns(&[3, 1]);
+        assert!(state.is_accept());
+        assert_eq!(state.patterns(), &[0]);
+
+        state.set_patterns(&[]);
+        assert!(!state.is_accept());
+    }
+
     #[test]
     pub fn test_basics() {
         let arena = Corrida::new(None);
@@ -277,12 +689,12 @@ mod test {
         println!("Partial: {:?}", start.elapsed());
     }
 
-    #[derive(Copy, Clone, Eq, PartialEq, Hash)]
+    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
     struct Binary {
         index: usize
     }
 
-    use super::Indexable;
+    use super::{FromIndex, Indexable};
 
     impl Indexable for Binary {
         fn get_index(&self) -> usize {
@@ -294,6 +706,12 @@ mod test {
         }
     }
 
+    impl FromIndex for Binary {
+        fn from_index(index: usize) -> Option<Self> {
+            (index < 2).then_some(Binary { index })
+        }
+    }
+
     #[test]
     fn test_big_string_complete() {
         let arena = Corrida::new(None);
@@ -359,6 +777,104 @@ mod test {
         dfa.simulate_slice(&[one,zero,zero,one]);
     }
 
+    #[test]
+    fn test_dfa_missing_transition_checked() {
+        let arena = Corrida::new(None);
+
+        dfa_state_creator!(($), new_state, arena, CompleteState<Binary>);
+        let one = Binary { index: 1 };
+        let zero = Binary { index: 0 };
+
+        let start_node = {
+            let s_0 = new_state!(true);
+            let s_1 = new_state!();
+            s_0.add_transition((zero, None));
+            s_0.add_transition((one,Some(s_1)));
+            s_1.add_transition((one, Some(s_0))); //s_1 has no transition on the zero symbol.
+
+            s_0
+        };
+
+        let dfa = Dfa::<Binary, CompleteState<Binary>>::new(start_node);
+
+        assert_eq!(dfa.validate(), vec![(1, zero)]);
+        assert_eq!(dfa.try_simulate(&[one, one, zero]), Ok(true));
+        assert_eq!(dfa.try_simulate(&[zero, one]), Ok(false));
+        assert_eq!(dfa.try_simulate(&[one, zero, zero, one]), Err(MissingTransition { state: 1, symbol: zero, position: 1 }));
+
+        for complete in [dfa.complete_with_dead_state(&arena), dfa.to_complete(&arena)] {
+            assert!(complete.validate().is_empty());
+            assert_eq!(complete.states().len(), 3);
+            assert!(!complete.simulate_slice(&[one, zero, zero, one]));
+            assert!(complete.simulate_slice(&[one, one, zero]));
+        }
+    }
+
+    #[test]
+    fn test_partial_complete_conversion() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<Binary>);
+        let one = Binary { index: 1 };
+        let zero = Binary { index: 0 };
+
+        // Accepts 1 0* 1, everything else runs off the partial DFA.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(false, &[(zero, None)]);
+            let s_2 = new_state!(true);
+            s_0.add_transition((one, Some(s_1)));
+            s_1.add_transition((one, Some(s_2)));
+            s_0
+        };
+
+        let partial = Dfa::<Binary, PartialState<Binary>>::new(start_node);
+        let complete = partial.to_complete(&arena);
+        assert!(complete.validate().is_empty());
+        assert_eq!(complete.states().len(), 4);
+
+        let back = complete.to_partial(&arena);
+        assert_eq!(back.states().len(), 3);
+
+        let tests: [&[Binary]; 6] = [&[], &[one, one], &[one, zero, zero, one], &[zero, one, one], &[one, one, one], &[one, zero]];
+        for input in tests {
+            let expected = partial.simulate_slice(input);
+            assert_eq!(complete.simulate_slice(input), expected);
+            assert_eq!(back.simulate_slice(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_reverse() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<char>);
+
+        // Accepts binary numbers divisible by 3, read most significant digit first.
+        let start_node = {
+            let s_0 = new_state!(true);
+            let s_1 = new_state!();
+            let s_2 = new_state!();
+            s_0.add_transition(('0', None));
+            s_0.add_transition(('1', Some(s_1)));
+            s_1.add_transition(('0', Some(s_2)));
+            s_1.add_transition(('1', Some(s_0)));
+            s_2.add_transition(('0', Some(s_1)));
+            s_2.add_transition(('1', None));
+            s_0
+        };
+
+        let dfa = Dfa::<char, PartialState<char>>::new(start_node);
+        let reverse = dfa.reverse::<2>(&arena);
+        let reverse_dfa = reverse.as_dfa(&arena);
+
+        for n in 0..200u32 {
+            let word: Vec<char> = format!("{:b}", n).chars().collect();
+            let reversed: Vec<char> = word.iter().rev().copied().collect();
+            assert_eq!(dfa.simulate_slice(&word), n % 3 == 0);
+            assert_eq!(reverse.simulate_slice(&reversed), n % 3 == 0);
+            assert_eq!(reverse_dfa.simulate_slice(&reversed), n % 3 == 0);
+        }
+    }
+
     #[test]
     fn test_dfa_repeated_transitions() {
         let arena = Corrida::new(None);
@@ -383,4 +899,83 @@ mod test {
         assert!(dfa.simulate_iter(vec!['1','0','0','1'].into_iter()));
 
     }
-}
\ No newline at end of file
+
+    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, gerber_derive::Indexable, gerber_derive::FromIndex)]
+    enum Base {
+        A,
+        C,
+        G,
+        T
+    }
+
+    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, gerber_derive::Indexable, gerber_derive::FromIndex)]
+    struct Byte(u8);
+
+    #[test]
+    fn test_indexable() {
+        assert_eq!(bool::count(), 2);
+        assert_eq!(u8::count(), 256);
+        assert_eq!(u16::count(), 65536);
+        assert_eq!(bool::from_index(true.get_index()), Some(true));
+        assert_eq!(bool::from_index(2), None);
+        assert_eq!(u8::from_index(255), Some(255));
+        assert_eq!(u8::from_index(256), None);
+        assert_eq!(u16::from_index(513u16.get_index()), Some(513));
+        assert_eq!(u16::from_index(65536), None);
+
+        assert_eq!(Base::count(), 4);
+        assert_eq!(Base::G.get_index(), 2);
+        assert_eq!(Base::from_index(3), Some(Base::T));
+        assert_eq!(Base::from_index(4), None);
+
+        assert_eq!(Byte::count(), 256);
+        assert_eq!(Byte(7).get_index(), 7);
+        assert_eq!(Byte::from_index(200), Some(Byte(200)));
+        assert_eq!(Byte::from_index(256), None);
+
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, CompleteState<Base>);
+
+        // Accepts sequences ending in G.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true, &[(Base::G, None)]);
+            for base in [Base::A, Base::C, Base::T] {
+                s_0.add_transition((base, None));
+                s_1.add_transition((base, Some(s_0)));
+            }
+            s_0.add_transition((Base::G, Some(s_1)));
+            s_0
+        };
+
+        let dfa = Dfa::<Base, CompleteState<Base>>::new(start_node);
+        assert!(dfa.simulate_slice(&[Base::A, Base::T, Base::G]));
+        assert!(!dfa.simulate_slice(&[Base::G, Base::C]));
+    }
+
+    #[test]
+    fn test_introspection() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<char>);
+
+        // Words over {a, b} ending in b, 'c' leads into a dead end.
+        let start_node = {
+            let s_0 = new_state!(false, &[('a', None)]);
+            let s_1 = new_state!(true, &[('b', None)]);
+            let dead = new_state!();
+            s_0.add_transition(('b', Some(s_1)));
+            s_1.add_transition(('a', Some(s_0)));
+            s_1.add_transition(('c', Some(dead)));
+            s_0
+        };
+
+        let dfa = Dfa::<char, PartialState<char>>::new(start_node);
+        assert_eq!(dfa.state_count(), 3);
+        assert_eq!(dfa.transition_count(), 5);
+        assert_eq!(dfa.accept_states(), vec![1]);
+        let mut alphabet = dfa.alphabet();
+        alphabet.sort_unstable();
+        assert_eq!(alphabet, vec!['a', 'b', 'c']);
+        assert!(std::ptr::eq(dfa.states()[0], start_node));
+    }
+}
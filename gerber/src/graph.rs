@@ -0,0 +1,64 @@
+use std::collections::{HashMap, HashSet};
+use std::ptr::NonNull;
+
+/// Returns every state reachable from the start through `successors`, in breadth first order starting with the start.
+/// The position of a state in the list is its id, the numbering used by traces, serialization and every other report on states.
+pub(crate) fn reachable<'a, T, I: IntoIterator<Item = &'a T>>(start: &'a T, mut successors: impl FnMut(&'a T) -> I) -> Vec<&'a T> {
+    let mut states = vec![start];
+    let mut seen = HashSet::from([start as *const T]);
+
+    let mut i = 0;
+    while i < states.len() {
+        for next in successors(states[i]) {
+            if seen.insert(next as *const T) {
+                states.push(next);
+            }
+        }
+        i += 1;
+    }
+
+    states
+}
+
+/// Maps every state to its position in the list.
+pub(crate) fn state_ids<T>(states: &[&T]) -> HashMap<*const T, usize> {
+    states.iter().enumerate().map(|(id, &state)| (state as *const T, id)).collect()
+}
+
+/// Returns which states can reach an accepting one, given the ids of the targets of every state.
+pub(crate) fn co_reachable(targets: &[Vec<usize>], accepting: &[bool]) -> Vec<bool> {
+    let mut sources = vec![Vec::new(); targets.len()];
+    for (id, targets) in targets.iter().enumerate() {
+        for &target in targets {
+            sources[target].push(id);
+        }
+    }
+
+    let mut live = accepting.to_vec();
+    let mut stack: Vec<usize> = (0..live.len()).filter(|&id| live[id]).collect();
+    while let Some(id) = stack.pop() {
+        for &source in &sources[id] {
+            if !live[source] {
+                live[source] = true;
+                stack.push(source);
+            }
+        }
+    }
+
+    live
+}
+
+/// Adds the transitions given as (source, label, target) ids between the states with `add`, which is called with the source state, the label and the target.
+/// The target is None for a self loop, which every state type reads as a transition back to itself.
+///
+/// # Safety
+/// Every state must be alive, and no reference to any of them may be held while this runs, as is the case for states just allocated in an arena.
+pub(crate) unsafe fn link<T, L>(states: &[NonNull<T>], edges: impl IntoIterator<Item = (usize, L, usize)>, mut add: impl FnMut(&mut T, L, Option<&T>)) {
+    for (source, label, target) in edges {
+        // Safety, no other references to the states exist. Self loops are passed as None, so the source is never borrowed mutably and shared at once.
+        unsafe {
+            let target = (target != source).then(|| &*states[target].as_ptr());
+            add(&mut *states[source].as_ptr(), label, target);
+        }
+    }
+}
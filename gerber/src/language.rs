@@ -0,0 +1,439 @@
+use std::hash::Hash;
+
+use crate::dfa::{Dfa, State};
+
+/// The length of the longest accepted word, found by cycle analysis on the live states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WordLength {
+    /// No word is accepted.
+    Empty,
+    /// The longest accepted word has this length.
+    Finite(usize),
+    /// A cycle through live states accepts arbitrarily long words.
+    Infinite,
+}
+
+/// Computes the longest accepted word of the graph starting at state 0, where `edges` lists the targets of every state.
+fn word_length(accept: &[bool], edges: &[Vec<usize>]) -> WordLength {
+    let mut incoming = vec![Vec::new(); edges.len()];
+    for (id, targets) in edges.iter().enumerate() {
+        for &target in targets {
+            incoming[target].push(id);
+        }
+    }
+
+    let mut live = accept.to_vec();
+    let mut stack: Vec<usize> = (0..accept.len()).filter(|&id| accept[id]).collect();
+    while let Some(id) = stack.pop() {
+        for &prev in &incoming[id] {
+            if !live[prev] {
+                live[prev] = true;
+                stack.push(prev);
+            }
+        }
+    }
+
+    if !live[0] {
+        return WordLength::Empty;
+    }
+
+    // Kahn's algorithm over the live subgraph, every state is reachable so any leftover state lies on a cycle.
+    let mut out_degree: Vec<usize> = edges.iter().map(|targets| targets.iter().filter(|&&target| live[target]).count()).collect();
+    let mut longest: Vec<Option<usize>> = accept.iter().map(|&accept| accept.then_some(0)).collect();
+    let mut ready: Vec<usize> = (0..edges.len()).filter(|&id| live[id] && out_degree[id] == 0).collect();
+    let mut sorted = 0;
+    while let Some(id) = ready.pop() {
+        sorted += 1;
+        for &prev in &incoming[id] {
+            if !live[prev] {
+                continue;
+            }
+            let through = longest[id].map(|len| len + 1);
+            longest[prev] = longest[prev].max(through);
+            out_degree[prev] -= 1;
+            if out_degree[prev] == 0 {
+                ready.push(prev);
+            }
+        }
+    }
+
+    if sorted < live.iter().filter(|&&live| live).count() {
+        WordLength::Infinite
+    } else {
+        WordLength::Finite(longest[0].expect("The start state is live."))
+    }
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Returns the accept flags and the targets of every state, indexed by breadth first state id.
+    fn graph(&self) -> (Vec<bool>, Vec<Vec<usize>>) {
+        let states = self.states();
+        let ids = self.state_ids();
+
+        let accept = states.iter().map(|state| state.is_accept()).collect();
+        let edges = states.iter()
+            .map(|state| state.transitions().map(|(_, target)| ids[&(target as *const S)]).collect())
+            .collect();
+
+        (accept, edges)
+    }
+
+    /// Returns, for every length up to `len`, the number of accepted words of that length starting from each state.
+    fn count_table(&self, len: usize) -> Vec<Vec<u128>> {
+        let (accept, edges) = self.graph();
+
+        let mut table: Vec<Vec<u128>> = vec![accept.iter().map(|&accept| accept as u128).collect()];
+        for _ in 0..len {
+            let counts = table.last().unwrap();
+            let next = edges.iter()
+                .map(|targets| targets.iter().fold(0u128, |sum, &target| sum.saturating_add(counts[target])))
+                .collect();
+            table.push(next);
+        }
+
+        table
+    }
+
+    /// Returns the number of words of exactly `len` symbols accepted by the DFA, saturating at `u128::MAX`.
+    pub fn count_accepted(&self, len: usize) -> u128 {
+        self.count_table(len)[len][0]
+    }
+
+    /// Returns true if the DFA accepts finitely many words.
+    pub fn is_finite(&self) -> bool {
+        let (accept, edges) = self.graph();
+        word_length(&accept, &edges) != WordLength::Infinite
+    }
+
+    /// Returns the length of the longest accepted word, or None if the DFA accepts no words or infinitely many.
+    pub fn max_word_len(&self) -> Option<usize> {
+        let (accept, edges) = self.graph();
+        match word_length(&accept, &edges) {
+            WordLength::Finite(len) => Some(len),
+            WordLength::Empty | WordLength::Infinite => None,
+        }
+    }
+
+    /// Returns an iterator over the words made of symbols from `alphabet` accepted by the DFA, in shortlex order.
+    /// Words are ordered by length first, and words of the same length by the order of their symbols in `alphabet`.
+    pub fn accepted_words(&self, alphabet: &[Σ]) -> AcceptedWords<Σ> {
+        let states = self.states();
+        let ids = self.state_ids();
+
+        let accept: Vec<bool> = states.iter().map(|state| state.is_accept()).collect();
+        let edges: Vec<Vec<Option<usize>>> = states.iter()
+            .map(|state| alphabet.iter().map(|&symbol| state.step(symbol).map(|target| ids[&(target as *const S)])).collect())
+            .collect();
+
+        let targets: Vec<Vec<usize>> = edges.iter().map(|row| row.iter().flatten().copied().collect()).collect();
+        let length = word_length(&accept, &targets);
+
+        AcceptedWords {
+            alphabet: alphabet.to_vec(),
+            edges,
+            accepts_in: vec![accept],
+            length,
+            len: 0,
+            path: Vec::new(),
+            word: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "rand")]
+/// Returns a uniformly random number below `bound`, rejecting draws from the incomplete last block to avoid modulo bias.
+fn random_below(rng: &mut impl rand::RngCore, bound: u128) -> u128 {
+    let zone = u128::MAX - u128::MAX % bound;
+    loop {
+        let draw = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+        if draw < zone {
+            return draw % bound;
+        }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Returns the accepted word of `len` symbols with the given rank, where `rank` is below the number of such words.
+    /// Every state splits the ranks between its transitions in proportion to the words accepted through them.
+    fn unrank(&self, table: &[Vec<u128>], len: usize, mut rank: u128) -> Vec<Σ> {
+        let ids = self.state_ids();
+        let mut cur = self.start_node;
+        let mut word = Vec::with_capacity(len);
+
+        for remaining in (0..len).rev() {
+            let (symbol, next) = cur.transitions()
+                .find(|&(_, target)| {
+                    let count = table[remaining][ids[&(target as *const S)]];
+                    if rank < count {
+                        true
+                    } else {
+                        rank -= count;
+                        false
+                    }
+                })
+                .expect("The rank is below the number of accepted words.");
+            word.push(symbol);
+            cur = next;
+        }
+
+        word
+    }
+
+    /// Draws a word uniformly among the accepted words of exactly `len` symbols, or returns None if there are none.
+    /// The draw is only uniform while the number of words fits in a `u128`, see `count_accepted`.
+    pub fn sample(&self, len: usize, rng: &mut impl rand::RngCore) -> Option<Vec<Σ>> {
+        let table = self.count_table(len);
+        let count = table[len][0];
+        (count > 0).then(|| self.unrank(&table, len, random_below(rng, count)))
+    }
+
+    /// Draws a word uniformly among the accepted words of at most `max_len` symbols, or returns None if there are none.
+    /// Longer lengths are picked more often, in proportion to the number of words of that length.
+    pub fn sample_up_to(&self, max_len: usize, rng: &mut impl rand::RngCore) -> Option<Vec<Σ>> {
+        let table = self.count_table(max_len);
+        let total = table.iter().fold(0u128, |sum, counts| sum.saturating_add(counts[0]));
+        if total == 0 {
+            return None;
+        }
+
+        let mut rank = random_below(rng, total);
+        for (len, counts) in table.iter().enumerate() {
+            if rank < counts[0] {
+                return Some(self.unrank(&table, len, rank));
+            }
+            rank -= counts[0];
+        }
+        unreachable!("The rank is below the total number of accepted words.")
+    }
+}
+
+// MARK: AcceptedWords
+/// An iterator over the accepted words of a DFA in shortlex order, created by `Dfa::accepted_words`.
+pub struct AcceptedWords<Σ> {
+    alphabet: Vec<Σ>,
+    edges: Vec<Vec<Option<usize>>>,
+    /// For every length `k` computed so far, whether each state accepts some word of exactly `k` symbols.
+    accepts_in: Vec<Vec<bool>>,
+    length: WordLength,
+    /// The length of the words currently being listed.
+    len: usize,
+    /// The states along the current word, paired with the index of the next symbol to try from them.
+    path: Vec<(usize, usize)>,
+    word: Vec<Σ>,
+}
+
+impl<Σ: Copy> AcceptedWords<Σ> {
+    /// Starts listing the words of the next length which has any, returning false once no lengths are left.
+    fn start_next_len(&mut self) -> bool {
+        loop {
+            match self.length {
+                WordLength::Empty => return false,
+                WordLength::Finite(max) if self.len > max => return false,
+                _ => {}
+            }
+
+            while self.accepts_in.len() <= self.len {
+                let last = self.accepts_in.last().unwrap();
+                let next = self.edges.iter()
+                    .map(|row| row.iter().flatten().any(|&target| last[target]))
+                    .collect();
+                self.accepts_in.push(next);
+            }
+
+            if self.accepts_in[self.len][0] {
+                self.path.push((0, 0));
+                return true;
+            }
+            self.len += 1;
+        }
+    }
+}
+
+impl<Σ: Copy> Iterator for AcceptedWords<Σ> {
+    type Item = Vec<Σ>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.path.is_empty() && !self.start_next_len() {
+                return None;
+            }
+
+            if self.word.len() == self.len {
+                let word = self.word.clone();
+                self.path.pop();
+                self.word.pop();
+                if self.path.is_empty() {
+                    self.len += 1;
+                }
+                return Some(word);
+            }
+
+            let remaining = self.len - self.word.len() - 1;
+            let (state, next_symbol) = self.path.last_mut().unwrap();
+            let found = (*next_symbol..self.alphabet.len()).find(|&symbol| {
+                self.edges[*state][symbol].is_some_and(|target| self.accepts_in[remaining][target])
+            });
+
+            match found {
+                Some(symbol) => {
+                    *next_symbol = symbol + 1;
+                    let target = self.edges[*state][symbol].unwrap();
+                    self.path.push((target, 0));
+                    self.word.push(self.alphabet[symbol]);
+                }
+                None => {
+                    self.path.pop();
+                    self.word.pop();
+                    if self.path.is_empty() {
+                        self.len += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dfa::PartialState;
+    use crate::dfa_state_creator;
+    use corrida::Corrida;
+
+    #[test]
+    fn test_infinite_language() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<u8>);
+
+        // Accepts words over {0, 1} with an even number of 1s, 2 leads into a dead state.
+        let start_node = {
+            let s_0 = new_state!(true, &[(0, None)]);
+            let s_1 = new_state!(false, &[(0, None), (1, Some(s_0))]);
+            let dead = new_state!();
+            s_0.add_transition((1, Some(s_1)));
+            s_0.add_transition((2, Some(dead)));
+            s_0
+        };
+
+        let dfa = Dfa::<u8, PartialState<u8>>::new(start_node);
+        assert!(!dfa.is_finite());
+        assert_eq!(dfa.max_word_len(), None);
+        assert_eq!(dfa.count_accepted(0), 1);
+        assert_eq!(dfa.count_accepted(3), 4);
+        assert_eq!(dfa.count_accepted(10), 512);
+        assert_eq!(dfa.count_accepted(200), u128::MAX);
+
+        let words: Vec<Vec<u8>> = dfa.accepted_words(&[0, 1, 2]).take(6).collect();
+        assert_eq!(words, vec![vec![], vec![0], vec![0, 0], vec![1, 1], vec![0, 0, 0], vec![0, 1, 1]]);
+
+        // Only words of ones, so every other length has no words.
+        let words: Vec<Vec<u8>> = dfa.accepted_words(&[1]).take(3).collect();
+        assert_eq!(words, vec![vec![], vec![1, 1], vec![1, 1, 1, 1]]);
+    }
+
+    #[test]
+    fn test_finite_language() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<char>);
+
+        // Accepts "a", "ab", "b" and "bb", with a cycle through states that can not reach an accept state.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true);
+            let s_2 = new_state!(true);
+            let s_3 = new_state!(true);
+            let trap = new_state!(false, &[('a', None)]);
+            s_0.add_transition(('a', Some(s_1)));
+            s_0.add_transition(('b', Some(s_2)));
+            s_1.add_transition(('b', Some(s_3)));
+            s_2.add_transition(('b', Some(s_3)));
+            s_3.add_transition(('a', Some(trap)));
+            s_0
+        };
+
+        let dfa = Dfa::<char, PartialState<char>>::new(start_node);
+        assert!(dfa.is_finite());
+        assert_eq!(dfa.max_word_len(), Some(2));
+        assert_eq!(dfa.count_accepted(2), 2);
+        assert_eq!(dfa.count_accepted(3), 0);
+
+        let words: Vec<String> = dfa.accepted_words(&['b', 'a']).map(|word| word.into_iter().collect()).collect();
+        assert_eq!(words, vec!["b", "a", "bb", "ab"]);
+
+        let empty = Dfa::<char, PartialState<char>>::new(new_state!(false, &[('a', None)]));
+        assert!(empty.is_finite());
+        assert_eq!(empty.max_word_len(), None);
+        assert_eq!(empty.accepted_words(&['a']).next(), None);
+    }
+
+    /// A xorshift generator, so the sampling tests are reproducible without pulling in a seeded rng.
+    #[cfg(feature = "rand")]
+    struct XorShift(u64);
+
+    #[cfg(feature = "rand")]
+    impl rand::RngCore for XorShift {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_sample() {
+        use std::collections::HashMap;
+
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<u8>);
+
+        // Accepts words over {0, 1} with an even number of 1s.
+        let start_node = {
+            let s_0 = new_state!(true, &[(0, None)]);
+            let s_1 = new_state!(false, &[(0, None), (1, Some(s_0))]);
+            s_0.add_transition((1, Some(s_1)));
+            s_0
+        };
+
+        let dfa = Dfa::<u8, PartialState<u8>>::new(start_node);
+        let mut rng = XorShift(0x2545F4914F6CDD1D);
+
+        let mut seen = HashMap::new();
+        for _ in 0..4000 {
+            let word = dfa.sample(4, &mut rng).unwrap();
+            assert_eq!(word.len(), 4);
+            assert!(dfa.simulate_slice(&word));
+            *seen.entry(word).or_insert(0) += 1;
+        }
+        // All 8 words are drawn, each close to 500 times.
+        assert_eq!(seen.len(), 8);
+        assert!(seen.values().all(|&count| (400..600).contains(&count)));
+
+        for _ in 0..100 {
+            let word = dfa.sample_up_to(3, &mut rng).unwrap();
+            assert!(word.len() <= 3 && dfa.simulate_slice(&word));
+        }
+
+        let empty = Dfa::<u8, PartialState<u8>>::new(new_state!(false, &[(0, None)]));
+        assert_eq!(empty.sample(3, &mut rng), None);
+        assert_eq!(empty.sample_up_to(3, &mut rng), None);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ptr::NonNull;
+
+use smallvec::Array;
+
+use crate::nfa::{Nfa, State};
+
+/// The number of times the cache may be cleared during a single run before the run falls back to NFA simulation.
+const DEFAULT_CLEAR_LIMIT: usize = 3;
+
+/// Cache statistics of a lazy DFA, accumulated over every run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LazyStats {
+    /// Transitions found in the cache.
+    pub hits: usize,
+    /// Transitions which had to be determinized.
+    pub misses: usize,
+    /// Times the cache was full and had to be cleared.
+    pub clears: usize,
+    /// Runs which cleared the cache too often and finished with NFA simulation.
+    pub fallbacks: usize,
+}
+
+/// A determinized subset of NFA states held in the cache.
+struct CachedState<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    subset: Vec<&'a State<TARGETS_HINT, Σ>>,
+    transitions: HashMap<Σ, usize>,
+    is_accept: bool,
+}
+
+type SubsetKey<const TARGETS_HINT: usize, Σ> = Vec<*const State<TARGETS_HINT, Σ>>;
+
+// MARK: LazyDfa
+/// A DFA which is built from an NFA while simulating, determinizing only the subsets the input actually reaches.
+///
+/// At most `max_states` subsets are cached. A full cache is cleared and rebuilt, and a run which clears it too often finishes with plain NFA simulation instead.
+pub struct LazyDfa<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    start_node: &'a State<TARGETS_HINT, Σ>,
+    states: Vec<CachedState<'a, TARGETS_HINT, Σ>>,
+    index: HashMap<SubsetKey<TARGETS_HINT, Σ>, usize>,
+    max_states: usize,
+    clear_limit: usize,
+    stats: LazyStats,
+}
+
+impl<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> Nfa<'a, State<TARGETS_HINT, Σ>>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    /// Returns a lazy DFA for this NFA, caching at most `max_states` determinized states.
+    pub fn lazy_dfa(&self, max_states: usize) -> LazyDfa<'a, TARGETS_HINT, Σ> {
+        LazyDfa::new(self, max_states)
+    }
+}
+
+impl<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> LazyDfa<'a, TARGETS_HINT, Σ>
+where
+    [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
+{
+    /// Creates a lazy DFA for the NFA with an empty cache holding at most `max_states` states, which must be at least 1.
+    pub fn new(nfa: &Nfa<'a, State<TARGETS_HINT, Σ>>, max_states: usize) -> Self {
+        assert!(max_states > 0, "The cache must hold at least one state.");
+        Self {
+            start_node: nfa.start_node,
+            states: Vec::new(),
+            index: HashMap::new(),
+            max_states,
+            clear_limit: DEFAULT_CLEAR_LIMIT,
+            stats: LazyStats::default(),
+        }
+    }
+
+    /// Sets how many times the cache may be cleared during a single run before falling back to NFA simulation.
+    pub fn with_clear_limit(mut self, clear_limit: usize) -> Self {
+        self.clear_limit = clear_limit;
+        self
+    }
+
+    /// Returns the cache statistics accumulated over every run so far.
+    pub fn stats(&self) -> LazyStats {
+        self.stats
+    }
+
+    /// Returns the number of states currently cached.
+    pub fn cached_states(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Extends the subset with its epsilon closure, and sorts it by address so equal subsets have equal keys.
+    fn close(mut subset: Vec<&'a State<TARGETS_HINT, Σ>>) -> (Vec<&'a State<TARGETS_HINT, Σ>>, SubsetKey<TARGETS_HINT, Σ>) {
+        let mut seen: HashSet<*const State<TARGETS_HINT, Σ>> = subset.iter().map(|&state| state as *const State<TARGETS_HINT, Σ>).collect();
+        let mut i = 0;
+        while i < subset.len() {
+            for next in subset[i].get_transitions(None) {
+                if seen.insert(next as *const State<TARGETS_HINT, Σ>) {
+                    subset.push(next);
+                }
+            }
+            i += 1;
+        }
+
+        subset.sort_unstable_by_key(|&state| state as *const State<TARGETS_HINT, Σ>);
+        let key = subset.iter().map(|&state| state as *const State<TARGETS_HINT, Σ>).collect();
+        (subset, key)
+    }
+
+    /// Returns the epsilon closed subset reached from the given subset by reading the symbol.
+    fn step(subset: &[&'a State<TARGETS_HINT, Σ>], symbol: Σ) -> (Vec<&'a State<TARGETS_HINT, Σ>>, SubsetKey<TARGETS_HINT, Σ>) {
+        let mut next = Vec::new();
+        let mut seen = HashSet::new();
+        for state in subset {
+            for target in state.get_transitions(Some(symbol)) {
+                if seen.insert(target as *const State<TARGETS_HINT, Σ>) {
+                    next.push(target);
+                }
+            }
+        }
+        Self::close(next)
+    }
+
+    /// Caches the subset as a new state, returning its index. The cache must not be full.
+    fn insert(&mut self, subset: Vec<&'a State<TARGETS_HINT, Σ>>, key: SubsetKey<TARGETS_HINT, Σ>) -> usize {
+        let id = self.states.len();
+        self.states.push(CachedState {
+            is_accept: subset.iter().any(|state| state.is_accept()),
+            subset,
+            transitions: HashMap::new(),
+        });
+        self.index.insert(key, id);
+        id
+    }
+
+    /// Returns the index of the cached start state, caching it if needed.
+    fn start(&mut self) -> usize {
+        let (subset, key) = Self::close(vec![self.start_node]);
+        match self.index.get(&key) {
+            Some(&id) => id,
+            None => {
+                if self.states.len() >= self.max_states {
+                    self.clear();
+                }
+                self.insert(subset, key)
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.index.clear();
+        self.stats.clears += 1;
+    }
+
+    /// Tests the provided input sequence on an iterator, returning true if the NFA accepts it.
+    pub fn simulate_iter(&mut self, mut input: impl Iterator<Item = Σ>) -> bool {
+        let mut cur = self.start();
+        let mut clears = 0;
+
+        while let Some(symbol) = input.next() {
+            if let Some(&next) = self.states[cur].transitions.get(&symbol) {
+                self.stats.hits += 1;
+                cur = next;
+                continue;
+            }
+
+            self.stats.misses += 1;
+            let (subset, key) = Self::step(&self.states[cur].subset, symbol);
+            if let Some(&next) = self.index.get(&key) {
+                self.states[cur].transitions.insert(symbol, next);
+                cur = next;
+            } else if self.states.len() < self.max_states {
+                let next = self.insert(subset, key);
+                self.states[cur].transitions.insert(symbol, next);
+                cur = next;
+            } else {
+                self.clear();
+                clears += 1;
+                if clears > self.clear_limit {
+                    self.stats.fallbacks += 1;
+                    return Self::simulate_subset(subset, input);
+                }
+                cur = self.insert(subset, key);
+            }
+        }
+
+        self.states[cur].is_accept
+    }
+
+    /// Tests the provided input sequence, returning true if the NFA accepts it.
+    pub fn simulate_slice(&mut self, input: &[Σ]) -> bool {
+        self.simulate_iter(input.iter().copied())
+    }
+
+    /// Finishes a run with NFA simulation, starting from an epsilon closed subset.
+    fn simulate_subset(mut subset: Vec<&'a State<TARGETS_HINT, Σ>>, input: impl Iterator<Item = Σ>) -> bool {
+        for symbol in input {
+            if subset.is_empty() {
+                return false;
+            }
+            subset = Self::step(&subset, symbol).0;
+        }
+        subset.iter().any(|state| state.is_accept())
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nfa_state_creator;
+    use corrida::Corrida;
+
+    /// Builds an NFA for words over {0, 1} whose fourth to last symbol is 1, which has 16 reachable DFA states.
+    fn fourth_to_last(arena: &Corrida) -> Nfa<'_, State<2, u8>> {
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+        let s_0 = new_state!(false, &[(Some(0), None), (Some(1), None)]);
+        let mut last = new_state!();
+        s_0.push_transition(Some(1), Some(last));
+        for _ in 0..3 {
+            let next = new_state!();
+            last.push_transition(Some(0), Some(next));
+            last.push_transition(Some(1), Some(next));
+            last = next;
+        }
+        last.set_accept(true);
+        Nfa::new(s_0)
+    }
+
+    fn words(len: usize) -> impl Iterator<Item = Vec<u8>> {
+        (0..1u32 << len).map(move |bits| (0..len).map(|i| ((bits >> i) & 1) as u8).collect())
+    }
+
+    #[test]
+    fn test_lazy_cache() {
+        let arena = Corrida::new(None);
+        let nfa = fourth_to_last(&arena);
+        let mut lazy = nfa.lazy_dfa(64);
+
+        for word in words(7) {
+            assert_eq!(lazy.simulate_slice(&word), nfa.simulate_slice(&word));
+        }
+
+        let stats = lazy.stats();
+        assert_eq!(lazy.cached_states(), 16);
+        assert_eq!(stats.misses, 32);
+        assert!(stats.hits > stats.misses);
+        assert_eq!(stats.clears, 0);
+        assert_eq!(stats.fallbacks, 0);
+    }
+
+    #[test]
+    fn test_lazy_thrashing() {
+        let arena = Corrida::new(None);
+        let nfa = fourth_to_last(&arena);
+        let mut lazy = nfa.lazy_dfa(4).with_clear_limit(2);
+
+        for word in words(9) {
+            assert_eq!(lazy.simulate_slice(&word), nfa.simulate_slice(&word));
+        }
+
+        let stats = lazy.stats();
+        assert!(stats.clears > 0);
+        assert!(stats.fallbacks > 0);
+        assert!(lazy.cached_states() <= 4);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use crate::dfa::{Dfa, State};
+use crate::PatternId;
+
+/// The kind of a token produced by the lexer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// The input in the span was matched by the given pattern.
+    Pattern(PatternId),
+    /// No pattern matches a non empty prefix of the input at this position, the span covers the single symbol that was skipped.
+    Error,
+}
+
+/// A token produced by the lexer, identifying the input it covers by its span.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Token {
+    /// What the token was matched as.
+    pub kind: TokenKind,
+    /// The range of the input covered by the token.
+    pub span: Range<usize>,
+}
+
+// MARK: Lexer
+/// Splits an input into tokens by running a multi-pattern DFA from the current position, emitting the longest match and restarting after it.
+///
+/// When several patterns accept the longest match, the lowest pattern id wins. Empty matches are never emitted, so the lexer always makes progress.
+pub struct Lexer<'a, 'i, Σ: Eq + Hash + Copy, S: State<Σ>> {
+    start_node: &'a S,
+    live: HashSet<*const S>,
+    input: &'i [Σ],
+    pos: usize,
+    _boo: PhantomData<Σ>
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Returns a lexer splitting the input into the tokens matched by this DFA, see `Nfa::union_patterns` for combining patterns.
+    pub fn lexer<'i>(&self, input: &'i [Σ]) -> Lexer<'a, 'i, Σ, S> {
+        Lexer {
+            start_node: self.start_node,
+            live: self.live_states(),
+            input,
+            pos: 0,
+            _boo: PhantomData
+        }
+    }
+}
+
+impl<'a, 'i, Σ: Eq + Hash + Copy, S: State<Σ>> Lexer<'a, 'i, Σ, S> {
+    /// Returns the position of the next symbol to be read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the end and pattern of the longest non empty match starting at the current position.
+    /// Stops reading as soon as a dead state is reached, since no longer match can be found past it.
+    fn longest_match(&self) -> Option<(usize, PatternId)> {
+        let mut cur = self.start_node;
+        let mut last = None;
+
+        for (i, &symbol) in self.input[self.pos..].iter().enumerate() {
+            match cur.step(symbol) {
+                Some(next) if self.live.contains(&(next as *const S)) => cur = next,
+                _ => break,
+            }
+            if let Some(&pattern) = cur.patterns().first() {
+                last = Some((self.pos + i + 1, pattern));
+            }
+        }
+
+        last
+    }
+}
+
+impl<Σ: Eq + Hash + Copy, S: State<Σ>> Iterator for Lexer<'_, '_, Σ, S> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.input.len() {
+            return None;
+        }
+
+        let start = self.pos;
+        let (end, kind) = match self.longest_match() {
+            Some((end, pattern)) => (end, TokenKind::Pattern(pattern)),
+            None => (start + 1, TokenKind::Error),
+        };
+        self.pos = end;
+
+        Some(Token {
+            kind,
+            span: start..end
+        })
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nfa::{Nfa, State};
+    use crate::nfa_state_creator;
+    use corrida::Corrida;
+
+    #[test]
+    fn test_lexer() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // 0: if, 1: [a-z]+, 2: [0-9]+, 3: ' '
+        let keyword = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            s_0.push_transition(Some(b'i'), Some(s_1));
+            s_1.push_transition(Some(b'f'), Some(s_2));
+            Nfa::new(&*s_0)
+        };
+        let identifier = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true);
+            for letter in b'a'..=b'z' {
+                s_0.push_transition(Some(letter), Some(s_1));
+                s_1.push_transition(Some(letter), None);
+            }
+            Nfa::new(&*s_0)
+        };
+        let number = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true);
+            for digit in b'0'..=b'9' {
+                s_0.push_transition(Some(digit), Some(s_1));
+                s_1.push_transition(Some(digit), None);
+            }
+            Nfa::new(&*s_0)
+        };
+        let space = Nfa::new(&*new_state!(false, &[(Some(b' '), Some(new_state!(true)))]));
+
+        let dfa = Nfa::union_patterns(&[keyword, identifier, number, space], &arena).as_dfa(&arena);
+        let tokens: Vec<Token> = dfa.lexer(b"if iff 42?x").collect();
+
+        let expected = [
+            (TokenKind::Pattern(0), 0..2),
+            (TokenKind::Pattern(3), 2..3),
+            (TokenKind::Pattern(1), 3..6),
+            (TokenKind::Pattern(3), 6..7),
+            (TokenKind::Pattern(2), 7..9),
+            (TokenKind::Error, 9..10),
+            (TokenKind::Pattern(1), 10..11),
+        ];
+        assert_eq!(tokens, expected.map(|(kind, span)| Token { kind, span }));
+    }
+
+    #[test]
+    fn test_lexer_backtracks() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // 0: a, 1: abc, the lexer must fall back to the last accept after reading "ab".
+        let short = Nfa::new(&*new_state!(false, &[(Some('a'), Some(new_state!(true)))]));
+        let long = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!();
+            let s_3 = new_state!(true);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(Some('b'), Some(s_2));
+            s_2.push_transition(Some('c'), Some(s_3));
+            Nfa::new(&*s_0)
+        };
+
+        let dfa = Nfa::union_patterns(&[short, long], &arena).as_dfa(&arena);
+        let input: Vec<char> = "abcaba".chars().collect();
+        let mut lexer = dfa.lexer(&input);
+
+        assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Pattern(1), span: 0..3 }));
+        assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Pattern(0), span: 3..4 }));
+        assert_eq!(lexer.position(), 4);
+        assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Error, span: 4..5 }));
+        assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Pattern(0), span: 5..6 }));
+        assert_eq!(lexer.next(), None);
+    }
+}
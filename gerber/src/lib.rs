@@ -4,7 +4,39 @@
 
 //! A simple DFA library to construct state machines, fast allocation using an a custom Arena implementation, and safe construction using Rust's borrow checker.
 
+// Lets the derive macros refer to `::gerber` from within this crate's own tests.
+extern crate self as gerber;
+
+/// Identifies one of the patterns combined into a multi-pattern automaton, lower ids take priority when several patterns match.
+pub type PatternId = u32;
+
 /// The DFA module contains the implementation of the Deterministic Finite Automaton.
 pub mod dfa;
 /// The NFA module contains the implementation of the Non-Deterministic Finite Automaton.
-pub mod nfa;
\ No newline at end of file
+pub mod nfa;
+/// The classes module contains symbol class computation and the class indexed DFA representation.
+pub mod classes;
+/// The cursor module contains a resumable DFA matcher for input that arrives in chunks.
+pub mod cursor;
+/// The search module contains unanchored searching for matches inside a larger haystack.
+pub mod search;
+/// The lexer module contains a longest match tokenizer driven by a multi-pattern DFA.
+pub mod lexer;
+/// The language module contains counting and enumeration of the words accepted by a DFA.
+pub mod language;
+/// The transducer module contains Moore and Mealy machines, which emit outputs while reading input.
+pub mod transducer;
+/// The trace module contains step by step execution traces and DOT export, both numbering states in breadth first order.
+pub mod trace;
+/// The owned module contains automata which carry the arena their states live in.
+pub mod owned;
+/// The serial module contains the numbered representation of automata, and its binary and JSON formats.
+pub mod serial;
+/// The lazy module contains a DFA which is determinized from an NFA on demand, with a bounded state cache.
+pub mod lazy;
+/// The bits module contains bit parallel simulation for NFAs with at most 128 states.
+pub mod bits;
+/// The regex module contains conversion of automata to regular expressions by state elimination.
+pub mod regex;
+/// The graph module contains the helpers every automaton uses to walk, number and copy its states.
+mod graph;
@@ -1,11 +1,15 @@
 use corrida::Corrida;
 use smallmap::Map;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::{collections::HashSet, ptr::NonNull};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::hash::Hash;
 use smallvec::{Array, SmallVec};
+use crate::bits::AnyBitNfa;
 use crate::dfa::{Dfa, PartialState, State as DfaState};
-use crate::dfa_state_creator;
+use crate::graph;
+use crate::PatternId;
 
 
 type Transitions<const TARGETS_HINT: usize, Σ> = SmallVec<[NonNull<State<{TARGETS_HINT}, Σ>>; TARGETS_HINT]>;
@@ -17,7 +21,7 @@ where
     [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
 {
     transitions: Map<Option<Σ>, Transitions<TARGETS_HINT, Σ>>,
-    is_accept: bool,
+    accept: Option<PatternId>,
 }
 
 /// An iterator over the targets of the transitions from a state for a given symbol.
@@ -51,11 +55,11 @@ impl<const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> State<TARGETS_HINT, Σ>
 where
     [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
 {
-    /// Creates a new state with no transitions and not accepting.
+    /// Creates a new state with no transitions, accepting states match pattern 0.
     pub fn new(is_accept: bool) -> Self {
         Self {
             transitions: Map::new(),
-            is_accept,
+            accept: is_accept.then_some(0),
         }
     }
 
@@ -77,9 +81,19 @@ where
         vec.push(target);
     }
 
-    /// Sets the state to be accepting or not.
+    /// Sets the state to be accepting or not, accepting states keep their pattern or match pattern 0.
     pub fn set_accept(&mut self, is_accept: bool) {
-        self.is_accept = is_accept;
+        self.accept = if is_accept { self.accept.or(Some(0)) } else { None };
+    }
+
+    /// Makes the state accept the given pattern.
+    pub fn set_pattern(&mut self, pattern: PatternId) {
+        self.accept = Some(pattern);
+    }
+
+    /// Returns the pattern matched in this state, if it is accepting.
+    pub fn pattern(&self) -> Option<PatternId> {
+        self.accept
     }
 
     /// Returns an iterator over the transitions for the given symbol.
@@ -93,7 +107,15 @@ where
 
     /// Returns if the state is accepting.
     pub fn is_accept(&self) -> bool {
-        self.is_accept
+        self.accept.is_some()
+    }
+
+    /// Returns every transition of the state, None symbols are epsilon transitions.
+    pub fn transitions(&self) -> impl Iterator<Item = (Option<Σ>, &Self)> {
+        self.transitions.iter().flat_map(|(symbol, targets)| {
+            // Safety, every transition points to a State allocated in the same arena as this one.
+            targets.iter().map(move |target| (*symbol, unsafe { &*target.as_ptr() }))
+        })
     }
 }
 
@@ -102,18 +124,15 @@ where
 macro_rules! nfa_state_creator {
     (($d: tt), $func_name: ident, $arena: expr, $symbol: ty, $TARGETS_HINT: expr) => {
         macro_rules! $func_name {
-            ($d($is_accept:expr $d(,$transitions: expr)?)? ) => {
+            () => {
+                $arena.alloc(State::<$TARGETS_HINT, $symbol>::new(false))
+            };
+            ($is_accept:expr $d(,$transitions: expr)? ) => {
                 {
-                    let mut accept_state = false;
-                    $d(
-                        accept_state = $is_accept;
-                    )?
-                    let new_state = $arena.alloc(State::<$TARGETS_HINT, $symbol>::new(accept_state));
+                    let new_state = $arena.alloc(State::<$TARGETS_HINT, $symbol>::new($is_accept));
                     $d(
-                        $d(
-                            let transitions: &[(_, Option<&State::<$TARGETS_HINT, $symbol>>)] = $transitions;
-                            transitions.iter().for_each(|&(symbol, target)| new_state.push_transition(symbol, target));
-                        )?
+                        let transitions: &[(_, Option<&State::<$TARGETS_HINT, $symbol>>)] = $transitions;
+                        transitions.iter().for_each(|&(symbol, target)| new_state.push_transition(symbol, target));
                     )?
                     new_state
                 }
@@ -122,13 +141,114 @@ macro_rules! nfa_state_creator {
     }
 }
 
+/// Decides which patterns a DFA state reports when the subset it was built from holds accept states of several patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchPolicy {
+    /// Only the lowest pattern id is reported, giving earlier patterns priority.
+    LowestId,
+    /// Every pattern id is reported.
+    All,
+}
+
+impl MatchPolicy {
+    /// Returns the patterns a DFA state reports given the patterns of the accept states in its subset, sorted and without duplicates.
+    fn resolve(self, accepted: impl Iterator<Item = PatternId>) -> SmallVec<[PatternId; 2]> {
+        let mut patterns: SmallVec<[PatternId; 2]> = accepted.collect();
+        patterns.sort_unstable();
+        patterns.dedup();
+        if self == MatchPolicy::LowestId {
+            patterns.truncate(1);
+        }
+        patterns
+    }
+}
+
+/// Upper bounds on the size of a DFA built by subset construction, None means unbounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of DFA states.
+    pub max_states: Option<usize>,
+    /// The maximum number of bytes used by DFA states and transitions, estimated from their in memory size.
+    pub max_bytes: Option<usize>,
+}
+
+/// The reason a bounded subset construction stopped before finishing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeterminizeError {
+    /// The DFA grew past the limits.
+    TooLarge {
+        /// The number of DFA states built when the limit was reached.
+        states_built: usize,
+    },
+    /// The cancellation callback asked to stop.
+    Cancelled,
+}
+
+impl Display for DeterminizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DeterminizeError::TooLarge { states_built } => write!(f, "subset construction exceeded its limits after building {} states", states_built),
+            DeterminizeError::Cancelled => write!(f, "subset construction was cancelled"),
+        }
+    }
+}
+
+impl Error for DeterminizeError {}
+
+/// Returned by the `try_` friendly simulations when the NFA has an epsilon cycle, which would make them loop forever.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EpsilonCycle;
+
+impl Display for EpsilonCycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the NFA has an epsilon cycle")
+    }
+}
+
+impl Error for EpsilonCycle {}
+
+/// Tracks the size of a DFA under construction against its limits.
+struct Budget {
+    limits: Limits,
+    states: usize,
+    bytes: usize,
+}
+
+impl Budget {
+    fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            states: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Accounts for a new state, failing if it would not fit in the limits.
+    fn add_state<S>(&mut self) -> Result<(), DeterminizeError> {
+        self.reserve(1, size_of::<S>())
+    }
+
+    /// Accounts for a new transition, failing if it would not fit in the limits.
+    fn add_transition<Σ, S>(&mut self) -> Result<(), DeterminizeError> {
+        self.reserve(0, size_of::<(Σ, NonNull<S>)>())
+    }
+
+    fn reserve(&mut self, states: usize, bytes: usize) -> Result<(), DeterminizeError> {
+        let (states, bytes) = (self.states + states, self.bytes + bytes);
+        if self.limits.max_states.is_some_and(|max| states > max) || self.limits.max_bytes.is_some_and(|max| bytes > max) {
+            return Err(DeterminizeError::TooLarge { states_built: self.states });
+        }
+        (self.states, self.bytes) = (states, bytes);
+        Ok(())
+    }
+}
+
 // MARK: NFA
 /// A non-deterministic fintie automaton.
 pub struct Nfa<'a ,T> {
-    start_node: &'a T,
+    pub(crate) start_node: &'a T,
 }
 
-type SymbolMapValue<'a, const TARGETS_HINT: usize, Σ> = (SmallVec<[&'a State<{TARGETS_HINT}, Σ>; 32]>, HashSet<*const State<TARGETS_HINT, Σ>>);
 impl<'a, const TARGETS_HINT:usize, Σ: Eq + Hash + Copy>  Nfa<'a, State<TARGETS_HINT, Σ>> 
 where 
     [NonNull<State<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<State<TARGETS_HINT, Σ>>>,
@@ -142,100 +262,445 @@ where
     }
 
 
-    //? Possibly my worst work yet.
-    /// Converts the NFA to a DFA using subset construction.
-    pub fn as_dfa(&self, arena: &Corrida) -> Dfa<'a, Σ, PartialState<Σ>> {
-        
-        dfa_state_creator!(($), new_state, arena, PartialState<Σ>);
-                
-        let mut hash_map = HashMap::new();
+    /// Builds a single NFA matching any of the given patterns in the arena, accept states of the i-th NFA report pattern id i.
+    /// Every NFA is copied, and a new start state has epsilon transitions to each copied start state.
+    pub fn union_patterns<'b>(patterns: &[Nfa<'_, State<TARGETS_HINT, Σ>>], arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        let start = arena.alloc(State::new(false));
+        for (id, nfa) in patterns.iter().enumerate() {
+            let copies = nfa.copy_states(arena);
+            for &copy in &copies {
+                // Safety, the copies were just allocated in the arena and no references to them are held.
+                unsafe {
+                    if (*copy.as_ptr()).is_accept() {
+                        (*copy.as_ptr()).set_pattern(id as PatternId);
+                    }
+                }
+            }
+            start.push_transition(None, Some(unsafe { copies[0].as_ref() }));
+        }
+
+        Nfa::new(start)
+    }
+
+    /// Converts the NFA to a DFA using subset construction, states reached by several patterns report all of them.
+    pub fn as_dfa<'b>(&self, arena: &'b Corrida) -> Dfa<'b, Σ, PartialState<Σ>> {
+        self.as_dfa_with(arena)
+    }
+
+    /// Converts the NFA to a DFA of any state type using subset construction, states reached by several patterns report all of them.
+    /// Complete DFAs, such as `as_dfa_with::<CompleteState<_>>`, route missing transitions to a generated dead state.
+    pub fn as_dfa_with<'b, S: DfaState<Σ> + Default>(&self, arena: &'b Corrida) -> Dfa<'b, Σ, S> {
+        self.as_dfa_with_policy(arena, MatchPolicy::All)
+    }
+
+    /// Converts the NFA to a DFA using subset construction, resolving the patterns of each DFA state with the given policy.
+    pub fn as_dfa_with_policy<'b, S: DfaState<Σ> + Default>(&self, arena: &'b Corrida, policy: MatchPolicy) -> Dfa<'b, Σ, S> {
+        self.determinize(arena, policy, Limits::default(), &mut || false).expect("Unbounded subset construction can not fail.")
+    }
+
+    /// Converts the NFA to a DFA using subset construction, failing once the DFA grows past the limits.
+    /// States built before the limit was reached stay allocated in the arena.
+    pub fn as_dfa_with_limits<'b, S: DfaState<Σ> + Default>(&self, arena: &'b Corrida, limits: Limits) -> Result<Dfa<'b, Σ, S>, DeterminizeError> {
+        self.determinize(arena, MatchPolicy::All, limits, &mut || false)
+    }
+
+    /// Converts the NFA to a DFA using subset construction, failing once the DFA grows past the limits or `cancel` returns true.
+    /// `cancel` is polled before every subset is expanded, so it should be cheap, such as reading an atomic flag or checking a deadline.
+    pub fn as_dfa_with_cancel<'b, S: DfaState<Σ> + Default>(&self, arena: &'b Corrida, limits: Limits, mut cancel: impl FnMut() -> bool) -> Result<Dfa<'b, Σ, S>, DeterminizeError> {
+        self.determinize(arena, MatchPolicy::All, limits, &mut cancel)
+    }
 
-        let set_hash = |set: &SmallVec<[&State<TARGETS_HINT, Σ>; 32]>| -> Vec<*const State<TARGETS_HINT, Σ>> {
-            set.iter().map(|&r| r as *const State<TARGETS_HINT, Σ>).collect()
+    /// Subset construction shared by every `as_dfa` variant.
+    /// Subsets are sorted ids into `states`, expanded in breadth first order with symbols in the order they are first seen, so the same NFA always gives the same DFA.
+    fn determinize<'b, S: DfaState<Σ> + Default>(&self, arena: &'b Corrida, policy: MatchPolicy, limits: Limits, cancel: &mut dyn FnMut() -> bool) -> Result<Dfa<'b, Σ, S>, DeterminizeError> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        // Returns the epsilon closure of the ids, sorted and without duplicates.
+        let closure = |mut subset: Vec<usize>| {
+            subset.sort_unstable();
+            subset.dedup();
+            let mut seen: HashSet<usize> = subset.iter().copied().collect();
+            let mut i = 0;
+            while i < subset.len() {
+                for next in states[subset[i]].get_transitions(None) {
+                    let next = ids[&(next as *const State<TARGETS_HINT, Σ>)];
+                    if seen.insert(next) {
+                        subset.push(next);
+                    }
+                }
+                i += 1;
+            }
+            subset.sort_unstable();
+            subset
         };
 
-        let mut current_states: SmallVec<[&State<TARGETS_HINT, Σ>; 32]> = SmallVec::from_elem(self.start_node, 1);
-        let mut set: HashSet<*const State<TARGETS_HINT, Σ>> = HashSet::from([self.start_node as *const State<TARGETS_HINT, Σ>]);
+        let new_state = |subset: &[usize]| {
+            let state = arena.alloc(S::default());
+            state.set_patterns(&policy.resolve(subset.iter().filter_map(|&id| states[id].pattern())));
+            NonNull::from(state)
+        };
 
-        let mut i = 0;
-        while i < current_states.len() {
-            let state = current_states[i];
-            for next in state.get_transitions(None) {
-                if set.insert(next as *const State<TARGETS_HINT, Σ>) {
-                    current_states.push(next);
+        let mut budget = Budget::new(limits);
+        let mut built: Vec<NonNull<S>> = Vec::new();
+        let mut index: HashMap<Vec<usize>, usize> = HashMap::new();
+        let mut queue = VecDeque::new();
+        let mut edges = Vec::new();
+
+        let start = closure(vec![0]);
+        budget.add_state::<S>()?;
+        built.push(new_state(&start));
+        index.insert(start.clone(), 0);
+        queue.push_back((start, 0));
+
+        while let Some((subset, id)) = queue.pop_front() {
+            if cancel() {
+                return Err(DeterminizeError::Cancelled);
+            }
+
+            let mut symbols: Vec<(Σ, Vec<usize>)> = Vec::new();
+            let mut positions: HashMap<Σ, usize> = HashMap::new();
+            for &state in &subset {
+                for (symbol, target) in states[state].transitions() {
+                    let Some(symbol) = symbol else { continue };
+                    let position = *positions.entry(symbol).or_insert_with(|| {
+                        symbols.push((symbol, Vec::new()));
+                        symbols.len() - 1
+                    });
+                    symbols[position].1.push(ids[&(target as *const State<TARGETS_HINT, Σ>)]);
                 }
             }
-            i += 1;
+
+            for (symbol, targets) in symbols {
+                let next = closure(targets);
+                let target_id = match index.get(&next) {
+                    Some(&target_id) => target_id,
+                    None => {
+                        budget.add_state::<S>()?;
+                        let target_id = built.len();
+                        built.push(new_state(&next));
+                        index.insert(next.clone(), target_id);
+                        queue.push_back((next, target_id));
+                        target_id
+                    }
+                };
+                budget.add_transition::<Σ, S>()?;
+                edges.push((id, symbol, target_id));
+            }
         }
 
-        // We have our start state now. 
-        let hash = set_hash(&current_states);
-        let mut is_accept = false;
-        for state in &current_states {
-            if state.is_accept {
-                is_accept = true;
-                break;
+        // Safety, the states were allocated in the arena by this function and are only reachable through `built`.
+        unsafe { graph::link(&built, edges, |state, symbol, target| state.add_transition((symbol, target))) };
+        S::finish_construction(&built, arena);
+        Ok(Dfa::from_start(unsafe { built[0].as_ref() }))
+    }
+
+    /// Returns every state reachable from the start node through symbol or epsilon transitions, in breadth first order starting with the start node.
+    /// The position of a state in the list is its id, the numbering used by traces, serialization and every other report on states.
+    pub fn states(&self) -> Vec<&'a State<TARGETS_HINT, Σ>> {
+        graph::reachable(self.start_node, |state| state.transitions().map(|(_, next)| next))
+    }
+
+    /// Returns the number of reachable states.
+    pub fn state_count(&self) -> usize {
+        self.states().len()
+    }
+
+    /// Returns the number of transitions between reachable states, counting every target of every symbol and epsilon transition.
+    pub fn transition_count(&self) -> usize {
+        self.states().iter().map(|state| state.transitions().count()).sum()
+    }
+
+    /// Returns every symbol with a transition from a reachable state, in the order they are first seen walking the states by id. Epsilon is not a symbol.
+    pub fn alphabet(&self) -> Vec<Σ> {
+        let mut seen = HashSet::new();
+        self.states().iter()
+            .flat_map(|state| state.transitions().filter_map(|(symbol, _)| symbol))
+            .filter(|&symbol| seen.insert(symbol))
+            .collect()
+    }
+
+    /// Returns the ids of the accept states, in increasing order.
+    pub fn accept_states(&self) -> Vec<usize> {
+        self.states().iter().enumerate().filter(|(_, state)| state.is_accept()).map(|(id, _)| id).collect()
+    }
+
+    /// Copies every reachable state into the arena, returning the copies in the order of `states`, so the copied start node comes first.
+    pub(crate) fn copy_states(&self, arena: &Corrida) -> Vec<NonNull<State<TARGETS_HINT, Σ>>> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let copies: Vec<NonNull<State<TARGETS_HINT, Σ>>> = states.iter()
+            .map(|state| {
+                let copy = arena.alloc(State::new(false));
+                copy.accept = state.accept;
+                NonNull::from(copy)
+            })
+            .collect();
+
+        let edges = states.iter().enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, target)| (id, symbol, target)))
+            .map(|(id, symbol, target)| (id, symbol, ids[&(target as *const State<TARGETS_HINT, Σ>)]));
+        // Safety, the copies were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&copies, edges, |copy, symbol, target| copy.push_transition(symbol, target)) };
+
+        copies
+    }
+
+    /// Builds an NFA in the arena matching a word of this NFA followed by a word of `other`, both are copied so they are left unchanged.
+    pub fn concat<'b>(&self, other: &Nfa<'_, State<TARGETS_HINT, Σ>>, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        let (start, accepts) = self.copy_parts(arena);
+        let (next, _) = other.copy_parts(arena);
+        for accept in accepts {
+            // Safety, the copies were just allocated in the arena and the accept state is never the start of the other copy.
+            unsafe {
+                (*accept.as_ptr()).set_accept(false);
+                (*accept.as_ptr()).push_transition(None, Some(next.as_ref()));
             }
         }
-        hash_map.insert(hash.clone(), (new_state!(is_accept) as *mut PartialState<Σ>, false));
-
-        
-        let mut queue = vec![(current_states, hash.clone())];
-        while let Some((subset, hash)) = queue.pop() {
-            let (my_dfa_node_ptr, processed) = hash_map.get_mut(&hash).unwrap();
-            let my_dfa_node = unsafe { my_dfa_node_ptr.as_mut().unwrap() };
-            *processed = true;
-
-
-            let mut symbol_map: HashMap<Σ, SymbolMapValue<'a, TARGETS_HINT, Σ>> = HashMap::new();
-            for state in subset {
-                for (symbol, next) in state.transitions.iter().filter(|(symbol, _)| symbol.is_some()) {
-                    let (vecb, setb) = symbol_map.entry(symbol.unwrap()).or_insert((SmallVec::new(), HashSet::new()));
-                    for next in next {
-                        if setb.insert(next.as_ptr()) {
-                            vecb.push(unsafe { next.as_ref() });
-                        }
+
+        Nfa::new(unsafe { start.as_ref() })
+    }
+
+    /// Builds an NFA in the arena matching the words of either this NFA or `other`, both are copied and keep their patterns.
+    pub fn union<'b>(&self, other: &Nfa<'_, State<TARGETS_HINT, Σ>>, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        let start = arena.alloc(State::new(false));
+        for nfa in [self.start_node, other.start_node] {
+            let (copy, _) = Nfa::new(nfa).copy_parts(arena);
+            start.push_transition(None, Some(unsafe { copy.as_ref() }));
+        }
+
+        Nfa::new(start)
+    }
+
+    /// Builds an NFA in the arena matching any number of words of this NFA, including none.
+    pub fn star<'b>(&self, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        self.repeat(0, None, arena)
+    }
+
+    /// Builds an NFA in the arena matching one or more words of this NFA.
+    pub fn plus<'b>(&self, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        self.repeat(1, None, arena)
+    }
+
+    /// Builds an NFA in the arena matching the empty word or a word of this NFA.
+    pub fn optional<'b>(&self, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        self.repeat(0, Some(1), arena)
+    }
+
+    /// Builds an NFA in the arena matching between `min` and `max` words of this NFA in a row, None meaning no upper bound.
+    /// The NFA is copied once per repetition, or `min` times and looped when unbounded. The empty word reports the lowest pattern of this NFA.
+    pub fn repeat<'b>(&self, min: usize, max: Option<usize>, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        assert!(max.is_none_or(|max| max >= min), "The maximum number of repetitions can not be below the minimum.");
+
+        let start = NonNull::from(arena.alloc(State::new(false)));
+        if min == 0 {
+            let pattern = self.states().iter().filter_map(|state| state.pattern()).min().unwrap_or(0);
+            // Safety, the state was just allocated in the arena and no references to it are held.
+            unsafe { (*start.as_ptr()).set_pattern(pattern) };
+        }
+
+        let copies = max.unwrap_or(min.max(1));
+        let mut ends = vec![start];
+        for k in 1..=copies {
+            let (copy, accepts) = self.copy_parts(arena);
+            // Safety, the copies were just allocated in the arena, and the states before a copy are never part of it.
+            unsafe {
+                for end in ends {
+                    (*end.as_ptr()).push_transition(None, Some(copy.as_ref()));
+                }
+                for &accept in &accepts {
+                    if k < min {
+                        (*accept.as_ptr()).set_accept(false);
+                    }
+                    if max.is_none() && k == copies && accept != copy {
+                        (*accept.as_ptr()).push_transition(None, Some(copy.as_ref()));
                     }
                 }
             }
+            ends = accepts;
+        }
+
+        Nfa::new(unsafe { start.as_ref() })
+    }
+
+    /// Copies every reachable state into the arena, returning the copied start state and the copied accept states.
+    fn copy_parts(&self, arena: &Corrida) -> (NonNull<State<TARGETS_HINT, Σ>>, Vec<NonNull<State<TARGETS_HINT, Σ>>>) {
+        let copies = self.copy_states(arena);
+        // Safety, the copies were just allocated in the arena and no mutable references to them are held.
+        let accepts = copies.iter().copied().filter(|copy| unsafe { copy.as_ref() }.is_accept()).collect();
+        (copies[0], accepts)
+    }
+
+    /// Builds an NFA for the reverse language in the arena, accepting `w` exactly when this NFA accepts `w` reversed.
+    /// Every transition is flipped, the old start state becomes the only accept state, and a new start state has epsilon transitions to all former accept states.
+    pub fn reverse<'b>(&self, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let reversed: Vec<NonNull<State<TARGETS_HINT, Σ>>> = states.iter()
+            .map(|&state| NonNull::from(arena.alloc(State::new(std::ptr::eq(state, self.start_node)))))
+            .collect();
+
+        let edges = states.iter().enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, target)| (target, symbol, id)))
+            .map(|(target, symbol, id)| (ids[&(target as *const State<TARGETS_HINT, Σ>)], symbol, id));
+        // Safety, the reversed states were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&reversed, edges, |state, symbol, target| state.push_transition(symbol, target)) };
+
+        let start = arena.alloc(State::new(false));
+        for (state, reversed) in states.iter().zip(&reversed) {
+            if state.is_accept() {
+                start.push_transition(None, Some(unsafe { reversed.as_ref() }));
+            }
+        }
+
+        Nfa::new(start)
+    }
 
-            for (vec, subset) in symbol_map.values_mut() {
-                let mut i = 0;
-                while i < vec.len() {
-                    let state = vec[i];
-                    for next in state.get_transitions(None) {
-                        if subset.insert(next as *const State<TARGETS_HINT, Σ>) {
-                            vec.push(next);
-                        }
+    /// Builds an equivalent NFA without epsilon transitions in the arena.
+    /// Every state takes over the symbol transitions of its epsilon closure and accepts if any state in it does, with the lowest pattern id. States only reachable through epsilon transitions are dropped.
+    pub fn remove_epsilons<'b>(&self, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let mut accepts = Vec::with_capacity(states.len());
+        let mut edges = Vec::with_capacity(states.len());
+        for id in 0..states.len() {
+            let mut closure = vec![id];
+            let mut seen = HashSet::from([id]);
+            let mut i = 0;
+            while i < closure.len() {
+                for next in states[closure[i]].get_transitions(None) {
+                    let next = ids[&(next as *const State<TARGETS_HINT, Σ>)];
+                    if seen.insert(next) {
+                        closure.push(next);
                     }
-                    i += 1;
                 }
+                i += 1;
             }
 
-            for (symbol, (subset, _)) in symbol_map {
-                let hash = set_hash(&subset);
-                let mut is_accept = false;
-                for state in &subset {
-                    if state.is_accept {
-                        is_accept = true;
-                        break;
+            let mut transitions = Vec::new();
+            let mut seen = HashSet::new();
+            for &member in &closure {
+                for (symbol, target) in states[member].transitions().filter(|(symbol, _)| symbol.is_some()) {
+                    let target = ids[&(target as *const State<TARGETS_HINT, Σ>)];
+                    if seen.insert((symbol, target)) {
+                        transitions.push((symbol, target));
                     }
                 }
-                let &mut (dfa_node, processed) = hash_map.entry(hash.clone()).or_insert_with(|| (new_state!(is_accept) as *mut PartialState<Σ>, false));
-                my_dfa_node.add_transition((symbol, Some(unsafe { dfa_node.as_ref().unwrap()})));
-                if !processed {
-                    queue.push((subset, hash));
+            }
+
+            accepts.push(closure.iter().filter_map(|&member| states[member].pattern()).min());
+            edges.push(transitions);
+        }
+
+        let mut reachable = vec![false; states.len()];
+        reachable[0] = true;
+        let mut stack = vec![0];
+        while let Some(id) = stack.pop() {
+            for &(_, target) in &edges[id] {
+                if !reachable[target] {
+                    reachable[target] = true;
+                    stack.push(target);
+                }
+            }
+        }
+
+        Self::build(arena, &accepts, &edges, &reachable)
+    }
+
+    /// Builds an equivalent NFA in the arena without the states that can not reach an accept state.
+    /// Only reachable states are ever copied, so the result is both accessible and co-accessible, apart from a start state which can not accept anything.
+    pub fn trim<'b>(&self, arena: &'b Corrida) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let accepts: Vec<Option<PatternId>> = states.iter().map(|state| state.pattern()).collect();
+        let edges: Vec<Vec<(Option<Σ>, usize)>> = states.iter()
+            .map(|state| state.transitions().map(|(symbol, target)| (symbol, ids[&(target as *const State<TARGETS_HINT, Σ>)])).collect())
+            .collect();
+
+        let targets: Vec<Vec<usize>> = edges.iter().map(|transitions| transitions.iter().map(|&(_, target)| target).collect()).collect();
+        let accepting: Vec<bool> = accepts.iter().map(Option::is_some).collect();
+        let live = graph::co_reachable(&targets, &accepting);
+
+        Self::build(arena, &accepts, &edges, &live)
+    }
+
+    /// Allocates the states described by their accept patterns and transitions by id in the arena, keeping only the states marked in `keep` and transitions between them.
+    /// State 0 becomes the start state and is always kept.
+    fn build<'b>(arena: &'b Corrida, accepts: &[Option<PatternId>], edges: &[Vec<(Option<Σ>, usize)>], keep: &[bool]) -> Nfa<'b, State<TARGETS_HINT, Σ>> {
+        // The position of every kept state among the copies.
+        let mut kept = vec![None; accepts.len()];
+        let mut copies: Vec<NonNull<State<TARGETS_HINT, Σ>>> = Vec::new();
+        for (id, &accept) in accepts.iter().enumerate() {
+            if keep[id] || id == 0 {
+                let state = arena.alloc(State::new(false));
+                state.accept = accept;
+                kept[id] = Some(copies.len());
+                copies.push(NonNull::from(state));
+            }
+        }
+
+        let edges = edges.iter().enumerate()
+            .flat_map(|(id, transitions)| transitions.iter().map(move |&(symbol, target)| (id, symbol, target)))
+            .filter_map(|(id, symbol, target)| Some((kept[id]?, symbol, kept[target]?)));
+        // Safety, the states were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&copies, edges, |state, symbol, target| state.push_transition(symbol, target)) };
+
+        Nfa::new(unsafe { copies[0].as_ref() })
+    }
+
+    /// Returns if some state can reach itself through epsilon transitions alone.
+    pub fn has_epsilon_cycle(&self) -> bool {
+        Self::epsilon_cycle(&self.states())
+    }
+
+    /// Finds epsilon cycles by repeatedly removing states without incoming epsilon transitions, a cycle is left if any state remains.
+    fn epsilon_cycle(states: &[&State<TARGETS_HINT, Σ>]) -> bool {
+        let ids = graph::state_ids(states);
+
+        let mut incoming = vec![0; states.len()];
+        for state in states {
+            for next in state.get_transitions(None) {
+                incoming[ids[&(next as *const State<TARGETS_HINT, Σ>)]] += 1;
+            }
+        }
+
+        let mut stack: Vec<usize> = (0..states.len()).filter(|&id| incoming[id] == 0).collect();
+        let mut removed = 0;
+        while let Some(id) = stack.pop() {
+            removed += 1;
+            for next in states[id].get_transitions(None) {
+                let next = ids[&(next as *const State<TARGETS_HINT, Σ>)];
+                incoming[next] -= 1;
+                if incoming[next] == 0 {
+                    stack.push(next);
                 }
             }
         }
 
-        Dfa::<Σ, PartialState<Σ>>::new(unsafe { hash_map.get(&hash).unwrap().0.as_ref().unwrap() })
+        removed < states.len()
     }
 
     /// Simulates the NFA on the given input, returning if the NFA accepts the input.
+    /// NFAs with at most 128 states are simulated with bit sets, compiled again on every call. Use `compile` to compile them once and reuse them.
     pub fn simulate_iter(&self, input: impl Iterator<Item = Σ>) -> bool {
-        let mut current_states: SmallVec<[&State<TARGETS_HINT, Σ>; 32]> = SmallVec::from_elem(self.start_node, 1);
-        let mut set: HashSet<*const State<TARGETS_HINT, Σ>> = HashSet::from([self.start_node as *const State<TARGETS_HINT, Σ>]);
+        match AnyBitNfa::new(&self.states()) {
+            Some(bits) => bits.simulate_iter(input),
+            None => self.simulate_sets(input),
+        }
+    }
+
+    /// Simulates the NFA on the given input with sets of states, never using bit sets.
+    pub(crate) fn simulate_sets(&self, input: impl Iterator<Item = Σ>) -> bool {
+        self.simulate_from(SmallVec::from_elem(self.start_node, 1), input)
+    }
+
+    /// Simulates the NFA on the given input starting from the epsilon closure of the given states, which must not contain duplicates.
+    fn simulate_from(&self, mut current_states: SmallVec<[&State<TARGETS_HINT, Σ>; 32]>, input: impl Iterator<Item = Σ>) -> bool {
+        let mut set: HashSet<*const State<TARGETS_HINT, Σ>> = current_states.iter().map(|&state| state as *const State<TARGETS_HINT, Σ>).collect();
         let mut next_states: SmallVec<[&State<TARGETS_HINT, Σ>; 32]> = SmallVec::new();
 
         let mut i = 0;
@@ -276,7 +741,7 @@ where
             (current_states, next_states) = (next_states, SmallVec::new());
         }
 
-        current_states.into_iter().any(|state| state.is_accept)
+        current_states.into_iter().any(|state| state.is_accept())
     }
 
     /// Simulates the NFA on the given input, returning if the NFA accepts the input.
@@ -284,13 +749,46 @@ where
         self.simulate_iter(input.iter().copied())
     }
 
-    /// Simulates the NFA on the given input, returning if the NFA accepts the input. Will infinite loop on epsilon loops, so only use for 'friendly' NFAs where specific states are not reached many times at the same token.
+    /// Simulates the NFA on the given input without deduplicating states, returning if the NFA accepts the input. Fast for 'friendly' NFAs where specific states are rarely reached many times at the same token.
+    /// Falls back to `simulate_iter` if the NFA has an epsilon cycle, or once the active states outnumber the states of the NFA.
     pub fn simulate_iter_friendly(&self, input: impl Iterator<Item = Σ>) -> bool {
+        let states = self.states();
+        if Self::epsilon_cycle(&states) {
+            return self.simulate_iter(input);
+        }
+        self.simulate_friendly(states.len(), input)
+    }
+
+    /// Simulates the NFA on the given input without deduplicating states, returning if the NFA accepts the input. Fast for 'friendly' NFAs where specific states are rarely reached many times at the same token.
+    /// Falls back to `simulate_iter` if the NFA has an epsilon cycle, or once the active states outnumber the states of the NFA.
+    pub fn simulate_slice_friendly(&self, input: &[Σ]) -> bool {
+        self.simulate_iter_friendly(input.iter().copied())
+    }
+
+    /// Simulates the NFA on the given input like `simulate_iter_friendly`, but returns an error instead of falling back if the NFA has an epsilon cycle.
+    pub fn try_simulate_iter_friendly(&self, input: impl Iterator<Item = Σ>) -> Result<bool, EpsilonCycle> {
+        let states = self.states();
+        if Self::epsilon_cycle(&states) {
+            return Err(EpsilonCycle);
+        }
+        Ok(self.simulate_friendly(states.len(), input))
+    }
+
+    /// Simulates the NFA on the given input like `simulate_slice_friendly`, but returns an error instead of falling back if the NFA has an epsilon cycle.
+    pub fn try_simulate_slice_friendly(&self, input: &[Σ]) -> Result<bool, EpsilonCycle> {
+        self.try_simulate_iter_friendly(input.iter().copied())
+    }
+
+    /// Simulation without deduplication for NFAs without epsilon cycles, switching to `simulate_from` once more than `limit` states are active.
+    fn simulate_friendly(&self, limit: usize, mut input: impl Iterator<Item = Σ>) -> bool {
         let mut current_states: SmallVec<[&State<TARGETS_HINT, Σ>; 32]> = SmallVec::from_elem(self.start_node, 1);
         let mut next_states: SmallVec<[&State<TARGETS_HINT, Σ>; 32]> = SmallVec::new();
 
         let mut i = 0;
         while i < current_states.len() {
+            if current_states.len() > limit {
+                return self.simulate_from(SmallVec::from_elem(self.start_node, 1), input);
+            }
             let state = current_states[i];
             for next in state.get_transitions(None) {
                 current_states.push(next);
@@ -299,8 +797,8 @@ where
         }
 
         //? In a well formed NFA, i believe that reaching the same state from two different paths is very rare.
-        for symbol in input {
-            for cur in current_states.into_iter() {
+        while let Some(symbol) = input.next() {
+            for cur in current_states.iter() {
                 for next in cur.get_transitions(Some(symbol)) {
                     next_states.push(next);
                 }
@@ -308,7 +806,11 @@ where
 
             let mut i = 0;
             while i < next_states.len() {
-                
+                if next_states.len() > limit {
+                    let mut seen = HashSet::new();
+                    current_states.retain(|state| seen.insert(*state as *const State<TARGETS_HINT, Σ>));
+                    return self.simulate_from(current_states, std::iter::once(symbol).chain(input));
+                }
                 for next in next_states[i].get_transitions(None) {
                     next_states.push(next);
                 }
@@ -317,12 +819,7 @@ where
             (current_states, next_states) = (next_states, SmallVec::new());
         }
 
-        current_states.into_iter().any(|state| state.is_accept)
-    }
-
-    /// Simulates the NFA on the given input, returning if the NFA accepts the input. Will infinite loop on epsilon loops, so only use for 'friendly' NFAs where specific states are not reached many times at the same token.
-    pub fn simulate_slice_friendly(&self, input: &[Σ]) -> bool {
-        self.simulate_iter_friendly(input.iter().copied())
+        current_states.into_iter().any(|state| state.is_accept())
     }
 }
 
@@ -332,6 +829,7 @@ mod test {
     use std::time::Instant;
 
     use super::*;
+    use crate::dfa::{CompleteState, PartialState};
 
     use corrida::Corrida;
 
@@ -454,8 +952,398 @@ mod test {
 
         println!("Loop -- NFA: {:?} DFA: {:?}", a, b);
     }
-}
 
+    #[test]
+    pub fn test_reverse() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // Accepts words over {0, 1} whose third to last symbol is 1, and ends with an epsilon loop back to the start.
+        let start_node = {
+            let s_0 = new_state!(false, &[(Some(1), None), (Some(0), None)]);
+            let s_1 = new_state!();
+            s_0.push_transition(Some(1), Some(s_1));
+            let s_2 = new_state!();
+            s_1.push_transition(Some(0), Some(s_2));
+            s_1.push_transition(Some(1), Some(s_2));
+            let s_3 = new_state!(true);
+            s_2.push_transition(Some(0), Some(s_3));
+            s_2.push_transition(Some(1), Some(s_3));
+            s_3.push_transition(None, Some(s_0));
+
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let reverse = nfa.reverse(&arena);
+
+        for len in 0..8 {
+            for bits in 0..(1u32 << len) {
+                let word: Vec<u8> = (0..len).map(|i| ((bits >> i) & 1) as u8).collect();
+                let reversed: Vec<u8> = word.iter().rev().copied().collect();
+                assert_eq!(nfa.simulate_slice(&word), reverse.simulate_slice(&reversed));
+            }
+        }
+    }
+
+    #[test]
+    fn test_union_patterns() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // if
+        let keyword = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            s_0.push_transition(Some('i'), Some(s_1));
+            s_1.push_transition(Some('f'), Some(s_2));
+            Nfa::new(&*s_0)
+        };
+
+        // [a-z]+
+        let identifier = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true);
+            for letter in 'a'..='z' {
+                s_0.push_transition(Some(letter), Some(s_1));
+                s_1.push_transition(Some(letter), None);
+            }
+            Nfa::new(&*s_0)
+        };
+
+        let union = Nfa::union_patterns(&[keyword, identifier], &arena);
+        let all = union.as_dfa_with_policy::<PartialState<_>>(&arena, MatchPolicy::All);
+        let lowest = union.as_dfa_with_policy::<PartialState<_>>(&arena, MatchPolicy::LowestId);
+
+        let word = |word: &str| word.chars().collect::<Vec<char>>();
+        assert_eq!(all.matching_patterns(&word("if")), &[0, 1]);
+        assert_eq!(lowest.matching_patterns(&word("if")), &[0]);
+        assert_eq!(lowest.matching_patterns(&word("i")), &[1]);
+        assert_eq!(lowest.matching_patterns(&word("iff")), &[1]);
+        assert!(lowest.matching_patterns(&word("")).is_empty());
+        assert!(lowest.matching_patterns(&word("if0")).is_empty());
+
+        assert!(union.as_dfa(&arena).simulate_slice(&word("if")));
+        assert!(!union.as_dfa(&arena).simulate_slice(&word("1")));
+    }
+
+    #[test]
+    fn test_limits() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // (a|b)*a(a|b){10}, the DFA has 2^11 states.
+        let start_node = {
+            let s_0 = new_state!(false, &[(Some('a'), None), (Some('b'), None)]);
+            let mut last = new_state!();
+            s_0.push_transition(Some('a'), Some(last));
+            for _ in 0..10 {
+                let next = new_state!();
+                last.push_transition(Some('a'), Some(next));
+                last.push_transition(Some('b'), Some(next));
+                last = next;
+            }
+            last.set_accept(true);
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let limited = |limits| nfa.as_dfa_with_limits::<PartialState<_>>(&arena, limits).err();
+
+        assert_eq!(limited(Limits { max_states: Some(100), max_bytes: None }), Some(DeterminizeError::TooLarge { states_built: 100 }));
+        assert!(matches!(limited(Limits { max_states: None, max_bytes: Some(1000) }), Some(DeterminizeError::TooLarge { states_built }) if states_built < 100));
+        assert_eq!(limited(Limits { max_states: Some(1 << 11), max_bytes: None }), None);
+
+        let mut polls = 0;
+        let cancelled = nfa.as_dfa_with_cancel::<PartialState<_>>(&arena, Limits::default(), || {
+            polls += 1;
+            polls > 10
+        });
+        assert_eq!(cancelled.err(), Some(DeterminizeError::Cancelled));
+        assert_eq!(polls, 11);
+
+        let dfa = nfa.as_dfa_with_cancel::<PartialState<_>>(&arena, Limits::default(), || false).unwrap();
+        let word: Vec<char> = "bbabbbbbbbbba".chars().collect();
+        assert_eq!(dfa.simulate_slice(&word), nfa.simulate_slice(&word));
+        assert!(dfa.simulate_slice(&word[2..]));
+    }
+
+    #[test]
+    fn test_as_complete_dfa() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, bool, 2);
+
+        // true* false, every other word needs a missing transition routed to the dead state.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(false, &[(Some(true), None)]);
+            let s_2 = new_state!(true);
+            s_0.push_transition(None, Some(s_1));
+            s_1.push_transition(Some(false), Some(s_2));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let dfa = nfa.as_dfa_with::<CompleteState<bool>>(&arena);
+        assert!(dfa.validate().is_empty());
+        for word in [&[false][..], &[true, true, false], &[], &[false, false], &[false, true], &[true]] {
+            assert_eq!(dfa.try_simulate(word), Ok(nfa.simulate_slice(word)));
+        }
+    }
+
+    #[test]
+    fn test_remove_epsilons() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // (0|1)*1 with every step behind epsilon transitions, including an epsilon cycle.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!();
+            let s_3 = new_state!(true);
+            s_0.push_transition(None, Some(s_1));
+            s_1.push_transition(None, Some(s_0));
+            s_1.push_transition(Some(0), Some(s_0));
+            s_1.push_transition(Some(1), Some(s_0));
+            s_1.push_transition(Some(1), Some(s_2));
+            s_2.push_transition(None, Some(s_3));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let free = nfa.remove_epsilons(&arena);
+
+        let states = free.states();
+        assert_eq!(states.len(), 2);
+        assert!(states.iter().all(|state| state.get_transitions(None).next().is_none()));
+        assert!(states[1].is_accept());
+
+        for len in 0..7 {
+            for bits in 0..(1u32 << len) {
+                let word: Vec<u8> = (0..len).map(|i| ((bits >> i) & 1) as u8).collect();
+                assert_eq!(free.simulate_slice(&word), nfa.simulate_slice(&word));
+            }
+        }
+    }
+
+    #[test]
+    fn test_trim() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // ab, with a dead end after 'a' and a dead loop on 'c'.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            let dead = new_state!(false, &[(Some('c'), None)]);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_0.push_transition(Some('c'), Some(dead));
+            s_1.push_transition(Some('b'), Some(s_2));
+            s_1.push_transition(None, Some(dead));
+            s_2.set_pattern(3);
+            s_0
+        };
+
+        let trimmed = Nfa::new(start_node).trim(&arena);
+        assert_eq!(trimmed.states().len(), 3);
+        assert!(trimmed.simulate_slice(&['a', 'b']));
+        assert!(!trimmed.simulate_slice(&['c']));
+        assert_eq!(trimmed.states()[2].pattern(), Some(3));
+
+        let never = Nfa::new(&*new_state!(false, &[(Some('a'), Some(new_state!()))])).trim(&arena);
+        assert_eq!(never.states().len(), 1);
+    }
+
+    #[test]
+    fn test_concat_union() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        let a = Nfa::new(&*new_state!(false, &[(Some('a'), Some(new_state!(true)))]));
+        let b = Nfa::new(&*new_state!(false, &[(Some('b'), Some(new_state!(true)))]));
+
+        let ab = a.concat(&b, &arena);
+        assert!(ab.simulate_slice(&['a', 'b']));
+        assert!(!ab.simulate_slice(&['a']));
+        assert!(!ab.simulate_slice(&['b']));
+
+        let a_or_ab = a.union(&ab, &arena);
+        assert!(a_or_ab.simulate_slice(&['a']));
+        assert!(a_or_ab.simulate_slice(&['a', 'b']));
+        assert!(!a_or_ab.simulate_slice(&['b']));
+        assert!(!a_or_ab.simulate_slice(&[]));
+
+        // The inputs are left unchanged.
+        assert!(a.simulate_slice(&['a']));
+        assert!(!a.simulate_slice(&['a', 'b']));
+    }
+
+    #[test]
+    fn test_repeat() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // ab
+        let ab = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            s_0.push_transition(Some(b'a'), Some(s_1));
+            s_1.push_transition(Some(b'b'), Some(s_2));
+            s_2.set_pattern(2);
+            Nfa::new(&*s_0)
+        };
+
+        let cases: [(Nfa<'_, State<2, u8>>, usize, Option<usize>); 6] = [
+            (ab.star(&arena), 0, None),
+            (ab.plus(&arena), 1, None),
+            (ab.optional(&arena), 0, Some(1)),
+            (ab.repeat(2, Some(3), &arena), 2, Some(3)),
+            (ab.repeat(3, None, &arena), 3, None),
+            (ab.repeat(0, Some(0), &arena), 0, Some(0)),
+        ];
+        for (nfa, min, max) in cases {
+            for count in 0..6 {
+                let word = b"ab".repeat(count);
+                let expected = count >= min && max.is_none_or(|max| count <= max);
+                assert_eq!(nfa.simulate_slice(&word), expected, "{count} repetitions of ab in {{{min}, {max:?}}}");
+                assert!(!nfa.simulate_slice(&[&word[..], b"a"].concat()));
+            }
+            assert!(nfa.states().iter().filter_map(|state| state.pattern()).all(|pattern| pattern == 2));
+        }
+    }
+
+    #[test]
+    fn test_epsilon_cycle() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // a*, with the loop going through an epsilon transition.
+        let start_node = {
+            let s_0 = new_state!(true);
+            let s_1 = new_state!();
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(None, Some(s_0));
+            s_0.push_transition(None, Some(s_1));
+            s_0
+        };
+
+        let cyclic = Nfa::new(start_node);
+        assert!(cyclic.has_epsilon_cycle());
+        assert!(cyclic.simulate_slice_friendly(&['a', 'a']));
+        assert!(!cyclic.simulate_slice_friendly(&['b']));
+        assert_eq!(cyclic.try_simulate_slice_friendly(&['a']), Err(EpsilonCycle));
+
+        let acyclic = cyclic.remove_epsilons(&arena);
+        assert!(!acyclic.has_epsilon_cycle());
+        assert_eq!(acyclic.try_simulate_slice_friendly(&['a', 'a']), Ok(true));
+
+        let self_loop = Nfa::new(&*new_state!(true, &[(None, None)]));
+        assert!(self_loop.has_epsilon_cycle());
+    }
+
+    #[test]
+    fn test_friendly_fallback() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // a?^n a^n, where reading a^n reaches the same states through exponentially many paths.
+        let n = 40;
+        let start_node = {
+            let s_0 = new_state!();
+            let mut last = &mut *s_0;
+            for i in 0..2 * n {
+                let next = new_state!(i + 1 == 2 * n);
+                last.push_transition(Some('a'), Some(next));
+                if i < n {
+                    last.push_transition(None, Some(next));
+                }
+                last = next;
+            }
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        assert!(!nfa.has_epsilon_cycle());
+        assert_eq!(nfa.try_simulate_slice_friendly(&vec!['a'; n]), Ok(true));
+        assert!(nfa.simulate_slice_friendly(&vec!['a'; 2 * n]));
+        assert!(!nfa.simulate_slice_friendly(&vec!['a'; n - 1]));
+    }
+
+    #[test]
+    fn test_analysis_follows_changes() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        let s_0 = new_state!();
+        let s_1 = new_state!();
+        s_0.push_transition(Some('a'), Some(s_1));
+        let nfa = Nfa::new(s_0);
+        assert_eq!(nfa.state_count(), 2);
+        assert!(!nfa.has_epsilon_cycle());
+
+        let s_2 = new_state!(true);
+        s_2.push_transition(None, Some(s_1));
+        s_1.push_transition(None, Some(s_2));
+        assert_eq!(nfa.state_count(), nfa.states().len());
+        assert_eq!(nfa.state_count(), 3);
+        assert!(nfa.has_epsilon_cycle());
+        assert!(nfa.simulate_slice_friendly(&['a']));
+    }
+
+    #[test]
+    fn test_introspection() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // a(b|c)*, with an epsilon transition into the loop.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true, &[(Some('b'), None), (Some('c'), None)]);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(None, Some(s_2));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        assert_eq!(nfa.state_count(), 3);
+        assert_eq!(nfa.transition_count(), 4);
+        assert_eq!(nfa.accept_states(), vec![2]);
+        assert_eq!(nfa.alphabet()[0], 'a');
+        assert_eq!(nfa.alphabet().len(), 3);
+
+        let states = nfa.states();
+        let targets: Vec<(Option<char>, *const State<2, char>)> = states[1].transitions().map(|(symbol, target)| (symbol, target as *const _)).collect();
+        assert_eq!(targets, vec![(None, states[2] as *const _)]);
+    }
+
+    #[test]
+    fn test_deterministic_subsets() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
 
+        // Both 'a' and 'b' reach {s_1, s_2}, but through the targets in a different order.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(true, &[(Some('c'), None)]);
+            let s_2 = new_state!(false, &[(Some('d'), None)]);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_0.push_transition(Some('a'), Some(s_2));
+            s_0.push_transition(Some('b'), Some(s_2));
+            s_0.push_transition(Some('b'), Some(s_1));
+            s_0
+        };
 
+        let nfa = Nfa::new(start_node);
+        let dfa = nfa.as_dfa(&arena);
+        assert_eq!(dfa.states().len(), 4);
+        assert_eq!(dfa.start_node.step('a').map(|s| s as *const _), dfa.start_node.step('b').map(|s| s as *const _));
 
+        let other_arena = Corrida::new(None);
+        assert_eq!(nfa.as_dfa(&other_arena).to_data(), dfa.to_data());
+    }
+}
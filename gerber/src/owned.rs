@@ -0,0 +1,134 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use corrida::Corrida;
+use smallvec::Array;
+
+use crate::dfa::{CompleteState, Dfa, FromIndex, PartialState, State};
+use crate::nfa::{Nfa, State as NfaState};
+
+// MARK: OwnedDfa
+/// A DFA which owns the arena its states live in, so it can be stored, returned and moved freely.
+pub struct OwnedDfa<Σ: Eq + Hash + Copy, S: State<Σ>> {
+    start_node: NonNull<S>,
+    // Blocks are heap allocated, so moving the arena never moves the states.
+    _arena: Corrida,
+    _boo: PhantomData<Σ>
+}
+
+impl<Σ: Eq + Hash + Copy, S: State<Σ>> OwnedDfa<Σ, S> {
+    /// Creates a new arena, builds a DFA in it with the provided function and bundles the two together.
+    pub fn new(build: impl for<'x> FnOnce(&'x Corrida) -> Dfa<'x, Σ, S>) -> Self {
+        let arena = Corrida::new(None);
+        let start_node = NonNull::from(build(&arena).start_node);
+        Self {
+            start_node,
+            _arena: arena,
+            _boo: PhantomData
+        }
+    }
+
+    /// Returns the DFA, borrowed for as long as this owner is.
+    pub fn dfa(&self) -> Dfa<'_, Σ, S> {
+        // Safety, the start node lives in the arena owned by self, and no mutable references to any state exist after construction.
+        Dfa::from_start(unsafe { self.start_node.as_ref() })
+    }
+}
+
+// Safety, the states are only reachable through the owner, and only hold symbols, pattern ids and pointers into the owned arena.
+unsafe impl<Σ: Eq + Hash + Copy + Send> Send for OwnedDfa<Σ, PartialState<Σ>> {}
+// Safety, the states are only reachable through the owner, and only hold symbols, pattern ids and pointers into the owned arena.
+unsafe impl<Σ: Eq + Hash + Copy + FromIndex + Send> Send for OwnedDfa<Σ, CompleteState<Σ>> {}
+
+// MARK: OwnedNfa
+/// An NFA which owns the arena its states live in, so it can be stored, returned and moved freely.
+pub struct OwnedNfa<T> {
+    start_node: NonNull<T>,
+    // Blocks are heap allocated, so moving the arena never moves the states.
+    _arena: Corrida,
+}
+
+impl<const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> OwnedNfa<NfaState<TARGETS_HINT, Σ>>
+where
+    [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+{
+    /// Creates a new arena, builds an NFA in it with the provided function and bundles the two together.
+    pub fn new(build: impl for<'x> FnOnce(&'x Corrida) -> Nfa<'x, NfaState<TARGETS_HINT, Σ>>) -> Self {
+        let arena = Corrida::new(None);
+        let start_node = NonNull::from(build(&arena).start_node);
+        Self {
+            start_node,
+            _arena: arena,
+        }
+    }
+
+    /// Returns the NFA, borrowed for as long as this owner is.
+    /// This only wraps the start node and costs nothing, the NFA caches no compiled form between calls, so use `compile` on it to reuse a bit NFA.
+    pub fn nfa(&self) -> Nfa<'_, NfaState<TARGETS_HINT, Σ>> {
+        // Safety, the start node lives in the arena owned by self, and no mutable references to any state exist after construction.
+        Nfa::new(unsafe { self.start_node.as_ref() })
+    }
+}
+
+// Safety, the states are only reachable through the owner, and only hold symbols, pattern ids and pointers into the owned arena.
+unsafe impl<const TARGETS_HINT: usize, Σ: Eq + Hash + Copy + Send> Send for OwnedNfa<NfaState<TARGETS_HINT, Σ>>
+where
+    [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+{}
+
+impl<const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> Nfa<'_, NfaState<TARGETS_HINT, Σ>>
+where
+    [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+{
+    /// Converts the NFA to a DFA using subset construction, in a new arena owned by the result.
+    pub fn into_owned_dfa(self) -> OwnedDfa<Σ, PartialState<Σ>> {
+        OwnedDfa::new(|arena| self.as_dfa(arena))
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nfa::State;
+    use crate::nfa_state_creator;
+
+    /// Builds an NFA for words over {0, 1} whose second to last symbol is 1, returning it together with its memory.
+    fn second_to_last() -> OwnedNfa<State<2, u8>> {
+        OwnedNfa::new(|arena| {
+            nfa_state_creator!(($), new_state, arena, u8, 2);
+            let s_0 = new_state!(false, &[(Some(0), None), (Some(1), None)]);
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            s_0.push_transition(Some(1), Some(s_1));
+            s_1.push_transition(Some(0), Some(s_2));
+            s_1.push_transition(Some(1), Some(s_2));
+            Nfa::new(s_0)
+        })
+    }
+
+    #[test]
+    fn test_owned_nfa() {
+        let owned = second_to_last();
+        assert!(owned.nfa().simulate_slice(&[0, 1, 0]));
+        assert!(!owned.nfa().simulate_slice(&[1, 0, 0]));
+
+        let dfa = owned.nfa().into_owned_dfa();
+        drop(owned);
+        assert!(dfa.dfa().simulate_slice(&[1, 1, 1]));
+        assert!(!dfa.dfa().simulate_slice(&[1]));
+    }
+
+    #[test]
+    fn test_owned_send() {
+        let dfa = second_to_last().nfa().into_owned_dfa();
+        let accepted = std::thread::spawn(move || {
+            (0..4u8).map(|bits| dfa.dfa().simulate_slice(&[bits >> 1, bits & 1])).collect::<Vec<_>>()
+        }).join().unwrap();
+        assert_eq!(accepted, vec![false, false, true, true]);
+
+        let nfa = second_to_last();
+        assert!(std::thread::spawn(move || nfa.nfa().simulate_slice(&[1, 0])).join().unwrap());
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ptr::NonNull;
+
+use smallvec::Array;
+
+use crate::dfa::{Dfa, State};
+use crate::graph;
+use crate::nfa::{Nfa, State as NfaState};
+
+/// A regular expression over chars, the empty language is represented by its absence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Regex {
+    Epsilon,
+    Symbol(char),
+    Concat(Vec<Regex>),
+    Union(Vec<Regex>),
+    Star(Box<Regex>),
+}
+
+impl Regex {
+    /// Returns if the expression matches the empty word.
+    fn nullable(&self) -> bool {
+        match self {
+            Regex::Epsilon | Regex::Star(_) => true,
+            Regex::Symbol(_) => false,
+            Regex::Concat(parts) => parts.iter().all(Regex::nullable),
+            Regex::Union(alternatives) => alternatives.iter().any(Regex::nullable),
+        }
+    }
+
+    /// Concatenates two expressions, dropping epsilons and flattening nested concatenations.
+    fn concat(self, other: Regex) -> Regex {
+        let mut parts = Vec::new();
+        for regex in [self, other] {
+            match regex {
+                Regex::Epsilon => {},
+                Regex::Concat(inner) => parts.extend(inner),
+                regex => parts.push(regex),
+            }
+        }
+
+        // x? x*, x* x? and x* x* are all x*
+        let mut i = 0;
+        while i + 1 < parts.len() {
+            let star = match (&parts[i], &parts[i + 1]) {
+                (Regex::Star(inner), other) | (other, Regex::Star(inner)) if other.covered_by(inner) => Regex::Star(inner.clone()),
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            parts.splice(i..i + 2, [star]);
+            i = i.saturating_sub(1);
+        }
+
+        match parts.len() {
+            0 => Regex::Epsilon,
+            1 => parts.pop().unwrap(),
+            _ => Regex::Concat(parts),
+        }
+    }
+
+    /// Joins two expressions with a union, removing duplicate alternatives, dropping epsilon when another alternative is nullable, and factoring out common prefixes and suffixes.
+    fn union(self, other: Regex) -> Regex {
+        let mut alternatives: Vec<Regex> = Vec::new();
+        for regex in [self, other] {
+            let flattened = match regex {
+                Regex::Union(inner) => inner,
+                regex => vec![regex],
+            };
+            for regex in flattened {
+                if !alternatives.contains(&regex) {
+                    alternatives.push(regex);
+                }
+            }
+        }
+
+        // ε | x x* = x*
+        if alternatives.contains(&Regex::Epsilon)
+            && let Some(plus) = alternatives.iter().position(|regex| regex.plus_body().is_some())
+        {
+            alternatives[plus] = Regex::Star(Box::new(alternatives[plus].plus_body().unwrap()));
+            alternatives.retain(|regex| *regex != Regex::Epsilon);
+        }
+
+        // x* covers ε, x and x x*
+        let stars: Vec<Regex> = alternatives.iter().filter(|regex| matches!(regex, Regex::Star(_))).cloned().collect();
+        alternatives.retain(|regex| !stars.iter().any(|star| {
+            let Regex::Star(inner) = star else { unreachable!() };
+            star != regex && (*regex == Regex::Epsilon || regex == &**inner || regex.plus_body().as_ref() == Some(&**inner))
+        }));
+
+        if alternatives.iter().any(|regex| *regex != Regex::Epsilon && regex.nullable()) {
+            alternatives.retain(|regex| *regex != Regex::Epsilon);
+        }
+
+        Self::factor(alternatives)
+    }
+
+    /// Returns if the expression is x* or x?, so that concatenating it with x* gives x* again. Plain x is not covered, since x x* is x+.
+    fn covered_by(&self, inner: &Regex) -> bool {
+        match self {
+            Regex::Star(other) => **other == *inner,
+            Regex::Union(alternatives) if alternatives.contains(&Regex::Epsilon) => {
+                let rest: Vec<Regex> = alternatives.iter().filter(|regex| **regex != Regex::Epsilon).cloned().collect();
+                rest.as_slice() == [inner.clone()] || Regex::Union(rest) == *inner
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns x if the expression is x x*, with x possibly spread over several parts of a concatenation.
+    fn plus_body(&self) -> Option<Regex> {
+        let Regex::Concat(parts) = self else { return None };
+        let (Regex::Star(inner), init) = parts.split_last()? else { return None };
+        let body = init.iter().cloned().fold(Regex::Epsilon, Regex::concat);
+        (body == **inner).then_some(body)
+    }
+
+    /// Builds the union of the alternatives, pulling out a first or last part shared by all of them.
+    fn factor(mut alternatives: Vec<Regex>) -> Regex {
+        if alternatives.len() == 1 {
+            return alternatives.pop().unwrap();
+        }
+
+        let parts = |regex: &Regex| match regex {
+            Regex::Epsilon => Vec::new(),
+            Regex::Concat(parts) => parts.clone(),
+            regex => vec![regex.clone()],
+        };
+        let split: Vec<Vec<Regex>> = alternatives.iter().map(parts).collect();
+        let rebuild = |parts: &[Regex]| parts.iter().cloned().fold(Regex::Epsilon, Regex::concat);
+
+        let prefix = split[0].first().filter(|&first| split.iter().all(|parts| parts.first() == Some(first)));
+        if let Some(prefix) = prefix {
+            let rest = split.iter().map(|parts| rebuild(&parts[1..])).reduce(Regex::union).unwrap();
+            return prefix.clone().concat(rest);
+        }
+
+        let suffix = split[0].last().filter(|&last| split.iter().all(|parts| parts.last() == Some(last)));
+        if let Some(suffix) = suffix {
+            let rest = split.iter().map(|parts| rebuild(&parts[..parts.len() - 1])).reduce(Regex::union).unwrap();
+            return rest.concat(suffix.clone());
+        }
+
+        Regex::Union(alternatives)
+    }
+
+    /// Repeats the expression any number of times, flattening nested stars and dropping nullable alternatives that a star already covers.
+    fn star(self) -> Regex {
+        match self {
+            Regex::Epsilon => Regex::Epsilon,
+            Regex::Star(inner) => Regex::Star(inner),
+            // (x | ε | y*)* = (x | y)*
+            Regex::Union(alternatives) => {
+                let inner = alternatives.into_iter()
+                    .filter(|regex| *regex != Regex::Epsilon)
+                    .map(|regex| match regex {
+                        Regex::Star(inner) => *inner,
+                        regex => regex,
+                    })
+                    .reduce(Regex::union);
+                match inner {
+                    Some(inner) if inner != Regex::Epsilon => Regex::Star(Box::new(inner)),
+                    _ => Regex::Epsilon,
+                }
+            },
+            // (x x*)* = x*
+            regex @ Regex::Concat(_) if regex.plus_body().is_some() => Regex::Star(Box::new(regex.plus_body().unwrap())),
+            regex => Regex::Star(Box::new(regex)),
+        }
+    }
+
+    /// Writes the expression in the syntax of `parse_regex`, an epsilon which is not the whole expression is written as `(|)`.
+    fn write(&self, out: &mut String) {
+        match self {
+            Regex::Epsilon => out.push_str("(|)"),
+            Regex::Symbol(symbol) => out.push(*symbol),
+            Regex::Union(alternatives) => {
+                let rest: Vec<&Regex> = alternatives.iter().filter(|regex| **regex != Regex::Epsilon).collect();
+                if rest.len() == alternatives.len() {
+                    for (i, regex) in rest.iter().enumerate() {
+                        if i > 0 {
+                            out.push('|');
+                        }
+                        regex.write(out);
+                    }
+                } else {
+                    match rest.as_slice() {
+                        [regex] => regex.write_atom(out),
+                        _ => Regex::Union(rest.into_iter().cloned().collect()).write_atom(out),
+                    }
+                    out.push('?');
+                }
+            },
+            Regex::Concat(parts) => {
+                let mut i = 0;
+                while i < parts.len() {
+                    // x x* is written as x+
+                    let plus = (i + 1..parts.len()).find_map(|j| {
+                        let body = Regex::Concat(parts[i..=j].to_vec()).plus_body()?;
+                        Some((j, body))
+                    });
+                    if let Some((j, body)) = plus {
+                        body.write_atom(out);
+                        out.push('+');
+                        i = j + 1;
+                        continue;
+                    }
+                    match &parts[i] {
+                        Regex::Union(alternatives) if !alternatives.contains(&Regex::Epsilon) => parts[i].write_atom(out),
+                        part => part.write(out),
+                    }
+                    i += 1;
+                }
+            },
+            Regex::Star(inner) => {
+                inner.write_atom(out);
+                out.push('*');
+            },
+        }
+    }
+
+    /// Writes the expression so that an operator can follow it, adding a group unless it is a single symbol.
+    fn write_atom(&self, out: &mut String) {
+        if let Regex::Symbol(symbol) = self {
+            out.push(*symbol);
+        } else {
+            out.push('(');
+            self.write(out);
+            out.push(')');
+        }
+    }
+}
+
+/// Adds an edge to the elimination graph, joining it with an existing edge between the same states.
+fn add_edge(outgoing: &mut [BTreeMap<usize, Regex>], incoming: &mut [BTreeSet<usize>], from: usize, to: usize, regex: Regex) {
+    let regex = match outgoing[from].remove(&to) {
+        Some(existing) => existing.union(regex),
+        None => regex,
+    };
+    outgoing[from].insert(to, regex);
+    incoming[to].insert(from);
+}
+
+/// Converts an automaton given by its transitions between state ids into a regular expression by state elimination, returning None if it accepts nothing.
+/// State 0 is the start state, epsilon transitions have no symbol.
+fn eliminate(count: usize, accepts: &[usize], transitions: &[(usize, Option<char>, usize)]) -> Option<String> {
+    // A new start and final state, so neither is ever eliminated.
+    let (start, end) = (count, count + 1);
+    let mut outgoing: Vec<BTreeMap<usize, Regex>> = vec![BTreeMap::new(); count + 2];
+    let mut incoming: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); count + 2];
+
+    add_edge(&mut outgoing, &mut incoming, start, 0, Regex::Epsilon);
+    for &accept in accepts {
+        add_edge(&mut outgoing, &mut incoming, accept, end, Regex::Epsilon);
+    }
+    for &(from, symbol, to) in transitions {
+        add_edge(&mut outgoing, &mut incoming, from, to, symbol.map_or(Regex::Epsilon, Regex::Symbol));
+    }
+
+    let mut remaining: BTreeSet<usize> = (0..count).collect();
+    while !remaining.is_empty() {
+        // Eliminating the state with the fewest paths through it keeps the expression small.
+        let state = *remaining.iter()
+            .min_by_key(|&&state| {
+                let sources = incoming[state].iter().filter(|&&source| source != state).count();
+                let targets = outgoing[state].keys().filter(|&&target| target != state).count();
+                sources * targets
+            })
+            .unwrap();
+        remaining.remove(&state);
+
+        let repeat = outgoing[state].remove(&state).map(Regex::star);
+        incoming[state].remove(&state);
+
+        let targets = std::mem::take(&mut outgoing[state]);
+        for target in targets.keys() {
+            incoming[*target].remove(&state);
+        }
+        for source in std::mem::take(&mut incoming[state]) {
+            let into = outgoing[source].remove(&state).unwrap();
+            for (&target, out) in &targets {
+                let path = match &repeat {
+                    Some(repeat) => into.clone().concat(repeat.clone()).concat(out.clone()),
+                    None => into.clone().concat(out.clone()),
+                };
+                add_edge(&mut outgoing, &mut incoming, source, target, path);
+            }
+        }
+    }
+
+    let regex = outgoing[start].remove(&end)?;
+    let mut out = String::new();
+    if regex != Regex::Epsilon {
+        regex.write(&mut out);
+    }
+    Some(out)
+}
+
+impl<const TARGETS_HINT: usize> Nfa<'_, NfaState<TARGETS_HINT, char>>
+where
+    [NonNull<NfaState<TARGETS_HINT, char>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, char>>>,
+{
+    /// Returns a regular expression matching the same words as the NFA in the syntax of `parse_regex`, or None if the NFA accepts nothing.
+    /// The syntax has no escapes, so symbols which are operators, such as `*` or `(`, are written as they are and will not parse back.
+    pub fn to_regex(&self) -> Option<String> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let transitions: Vec<(usize, Option<char>, usize)> = states.iter()
+            .enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, target)| (id, symbol, target)))
+            .map(|(id, symbol, target)| (id, symbol, ids[&(target as *const NfaState<TARGETS_HINT, char>)]))
+            .collect();
+
+        eliminate(states.len(), &self.accept_states(), &transitions)
+    }
+}
+
+impl<S: State<char>> Dfa<'_, char, S> {
+    /// Returns a regular expression matching the same words as the DFA in the syntax of `parse_regex`, or None if the DFA accepts nothing.
+    /// The syntax has no escapes, so symbols which are operators, such as `*` or `(`, are written as they are and will not parse back.
+    pub fn to_regex(&self) -> Option<String> {
+        let states = self.states();
+        let ids = self.state_ids();
+
+        let transitions: Vec<(usize, Option<char>, usize)> = states.iter()
+            .enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, target)| (id, symbol, target)))
+            .map(|(id, symbol, target)| (id, Some(symbol), ids[&(target as *const S)]))
+            .collect();
+
+        eliminate(states.len(), &self.accept_states(), &transitions)
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nfa::State;
+    use crate::nfa_state_creator;
+    use corrida::Corrida;
+
+    #[test]
+    fn test_simplify() {
+        let symbol = Regex::Symbol;
+        assert_eq!(symbol('a').union(Regex::Epsilon).union(symbol('a').star()), symbol('a').star());
+        assert_eq!(symbol('a').star().star(), symbol('a').star());
+        assert_eq!(symbol('a').union(Regex::Epsilon).star(), symbol('a').star());
+
+        let ab = symbol('a').concat(symbol('b'));
+        let ac = symbol('a').concat(symbol('c'));
+        assert_eq!(ab.union(ac), symbol('a').concat(symbol('b').union(symbol('c'))));
+
+        let a_or_b = symbol('a').union(symbol('b'));
+        assert_eq!(a_or_b.clone().star().concat(a_or_b.clone().union(Regex::Epsilon)), a_or_b.star());
+
+        let mut out = String::new();
+        symbol('a').concat(symbol('b').union(Regex::Epsilon)).concat(symbol('c')).concat(symbol('c').star()).write(&mut out);
+        assert_eq!(out, "ab?c+");
+    }
+
+    #[test]
+    fn test_to_regex() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // a(b|c)*, with an epsilon transition into the loop.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true, &[(Some('b'), None), (Some('c'), None)]);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(None, Some(s_2));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let regex = nfa.to_regex().unwrap();
+        assert!(regex == "a(b|c)*" || regex == "a(c|b)*", "{regex}");
+        let regex = nfa.as_dfa(&arena).to_regex().unwrap();
+        assert!(regex == "a(b|c)*" || regex == "a(c|b)*", "{regex}");
+
+        assert_eq!(Nfa::new(&*new_state!(true)).to_regex(), Some(String::new()));
+        assert_eq!(Nfa::new(&*new_state!(false, &[(Some('a'), None)])).to_regex(), None);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::{Deref, Range};
+use std::ptr::NonNull;
+
+use corrida::Corrida;
+use smallvec::Array;
+
+use crate::dfa::{Dfa, PartialState, State};
+use crate::nfa::{Nfa, State as NfaState};
+
+/// Selects which match is reported when several matches overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Reports the leftmost match. Among the matches starting there the lowest pattern id wins, and the match ends at the last position where that pattern matches, so with a single pattern this is the longest match.
+    LeftmostFirst,
+    /// Reports the leftmost match, and the longest one among the matches starting there whatever its pattern.
+    LeftmostLongest,
+    /// Reports the match which ends first, stopping the forward scan at the first accept state reached. Among the matches with that end, the leftmost one is reported.
+    Earliest,
+}
+
+// MARK: Searcher
+/// Finds matches of a DFA inside a larger haystack, with a forward scan for the end of a match and a reverse anchored scan from that end for its start.
+///
+/// The reverse DFA is built once, when the searcher is created, and reused for every search.
+pub struct Searcher<'a, Σ: Eq + Hash + Copy, S: State<Σ>> {
+    start_node: NonNull<S>,
+    live: HashSet<*const S>,
+    reverse: NonNull<PartialState<Σ>>,
+    kind: MatchKind,
+    // Holds the reverse DFA, and the forward DFA when it was determinized from an NFA. Blocks are heap allocated, so moving the arena never moves the states.
+    _arena: Corrida,
+    _boo: PhantomData<&'a ()>,
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Returns a searcher for this DFA, building its reverse DFA.
+    pub fn searcher(&self, kind: MatchKind) -> Searcher<'a, Σ, S> {
+        let arena = Corrida::new(None);
+        let reverse = NonNull::from(self.reverse::<2>(&arena).as_dfa(&arena).start_node);
+        Searcher {
+            start_node: NonNull::from(self.start_node),
+            live: self.live_states(),
+            reverse,
+            kind,
+            _arena: arena,
+            _boo: PhantomData,
+        }
+    }
+
+    /// Returns the range of the first match in the haystack, chosen by `kind`.
+    /// Builds a new searcher on every call, use `searcher` to reuse one across haystacks.
+    pub fn find(&self, haystack: &[Σ], kind: MatchKind) -> Option<Range<usize>> {
+        self.searcher(kind).find(haystack)
+    }
+
+    /// Returns an iterator over the non overlapping matches in the haystack, from left to right.
+    /// Builds a new searcher for the iterator, use `searcher` to reuse one across haystacks.
+    pub fn find_iter<'h>(&self, haystack: &'h [Σ], kind: MatchKind) -> FindIter<'h, Σ, Box<Searcher<'a, Σ, S>>> {
+        FindIter::new(Box::new(self.searcher(kind)), haystack)
+    }
+}
+
+impl<const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> Nfa<'_, NfaState<TARGETS_HINT, Σ>>
+where
+    [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+{
+    /// Returns a searcher for this NFA, building its forward and reverse DFAs.
+    pub fn searcher(&self, kind: MatchKind) -> Searcher<'static, Σ, PartialState<Σ>> {
+        let arena = Corrida::new(None);
+        let forward = self.as_dfa(&arena);
+        let live = forward.live_states();
+        let start_node = NonNull::from(forward.start_node);
+        let reverse = NonNull::from(self.reverse(&arena).as_dfa(&arena).start_node);
+        Searcher {
+            start_node,
+            live,
+            reverse,
+            kind,
+            _arena: arena,
+            _boo: PhantomData,
+        }
+    }
+
+    /// Returns the range of the first match in the haystack, chosen by `kind`.
+    /// Builds a new searcher on every call, use `searcher` to reuse one across haystacks.
+    pub fn find(&self, haystack: &[Σ], kind: MatchKind) -> Option<Range<usize>> {
+        self.searcher(kind).find(haystack)
+    }
+
+    /// Returns an iterator over the non overlapping matches in the haystack, from left to right.
+    /// Builds a new searcher for the iterator, use `searcher` to reuse one across haystacks.
+    pub fn find_iter<'h>(&self, haystack: &'h [Σ], kind: MatchKind) -> FindIter<'h, Σ, Box<Searcher<'static, Σ, PartialState<Σ>>>> {
+        FindIter::new(Box::new(self.searcher(kind)), haystack)
+    }
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Searcher<'a, Σ, S> {
+    /// Returns the range of the first match in the haystack, chosen by the kind of the searcher.
+    pub fn find(&self, haystack: &[Σ]) -> Option<Range<usize>> {
+        self.find_at(haystack, 0)
+    }
+
+    /// Returns an iterator over the non overlapping matches in the haystack, from left to right.
+    pub fn find_iter<'s, 'h>(&'s self, haystack: &'h [Σ]) -> FindIter<'h, Σ, &'s Self> {
+        FindIter::new(self, haystack)
+    }
+
+    fn start_node(&self) -> &S {
+        // Safety, the start node lives either in the arena of the DFA this searcher borrows for 'a, or in the arena owned by self.
+        unsafe { self.start_node.as_ref() }
+    }
+
+    fn reverse(&self) -> &PartialState<Σ> {
+        // Safety, the reverse DFA lives in the arena owned by self.
+        unsafe { self.reverse.as_ref() }
+    }
+
+    /// Returns the range of the first match in the haystack which starts at or after `from`.
+    fn find_at(&self, haystack: &[Σ], from: usize) -> Option<Range<usize>> {
+        let end = self.match_end(haystack, from)?;
+        Some(self.match_start(haystack, from, end)..end)
+    }
+
+    /// Runs the DFA forward from `from`, starting a new run at every position until a match is seen, and returns the end of the match chosen by the kind.
+    ///
+    /// Runs are kept in the order they started, so once one of them accepts it leads and every later run is dropped. An earlier run accepting afterwards takes the lead.
+    /// The scan stops when no run is left, or at the first accept for `Earliest`.
+    fn match_end(&self, haystack: &[Σ], from: usize) -> Option<usize> {
+        let start_node = self.start_node() as *const S;
+        if !self.live.contains(&start_node) {
+            return None;
+        }
+
+        let mut runs: Vec<&S> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut end = None;
+        // Whether the last run is the one which accepted last, and the pattern it matched.
+        let mut leader = false;
+        let mut pattern = 0;
+        let mut pos = from;
+        loop {
+            if end.is_none() && seen.insert(start_node) {
+                runs.push(self.start_node());
+            }
+            if let Some(first) = runs.iter().position(|run| run.is_accept()) {
+                let matched = runs[first].patterns().first().copied().unwrap_or(0);
+                if leader && first == runs.len() - 1 && self.kind == MatchKind::LeftmostFirst {
+                    // The leading run matched again, only its best pattern may extend the match.
+                    if matched <= pattern {
+                        (pattern, end) = (matched, Some(pos));
+                    }
+                } else {
+                    runs.truncate(first + 1);
+                    (leader, pattern, end) = (true, matched, Some(pos));
+                }
+                if self.kind == MatchKind::Earliest {
+                    break;
+                }
+            }
+            if pos == haystack.len() || (end.is_some() && runs.is_empty()) {
+                break;
+            }
+
+            seen.clear();
+            let last = runs.len().wrapping_sub(1);
+            let mut next = Vec::with_capacity(runs.len() + 1);
+            let mut leader_alive = false;
+            for (i, run) in runs.into_iter().enumerate() {
+                if let Some(target) = run.step(haystack[pos]) && self.live.contains(&(target as *const S)) && seen.insert(target as *const S) {
+                    next.push(target);
+                    leader_alive = i == last;
+                }
+            }
+            leader &= leader_alive;
+            runs = next;
+            pos += 1;
+        }
+
+        end
+    }
+
+    /// Runs the reverse DFA backwards from `end`, returning the leftmost position not before `from` at which a match ending at `end` starts.
+    fn match_start(&self, haystack: &[Σ], from: usize, end: usize) -> usize {
+        let mut cur = self.reverse();
+        let mut start = cur.is_accept().then_some(end);
+
+        for pos in (from..end).rev() {
+            match cur.step(haystack[pos]) {
+                Some(next) => cur = next,
+                None => break,
+            }
+            if cur.is_accept() {
+                start = Some(pos);
+            }
+        }
+
+        start.expect("The forward scan found a match ending here.")
+    }
+}
+
+// MARK: FindIter
+/// An iterator over the non overlapping matches in a haystack, from left to right.
+/// Holds its searcher either borrowed or boxed.
+pub struct FindIter<'h, Σ, R> {
+    searcher: R,
+    haystack: &'h [Σ],
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl<'h, Σ, R> FindIter<'h, Σ, R> {
+    fn new(searcher: R, haystack: &'h [Σ]) -> Self {
+        Self {
+            searcher,
+            haystack,
+            pos: 0,
+            last_end: None,
+        }
+    }
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>, R: Deref<Target = Searcher<'a, Σ, S>>> Iterator for FindIter<'_, Σ, R> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos <= self.haystack.len() {
+            let Some(found) = self.searcher.find_at(self.haystack, self.pos) else {
+                break;
+            };
+            // Empty matches would otherwise be found at the same position forever, and one right after the previous match is skipped.
+            self.pos = if found.is_empty() { found.end + 1 } else { found.end };
+            if found.is_empty() && self.last_end == Some(found.start) {
+                continue;
+            }
+            self.last_end = Some(found.end);
+            return Some(found);
+        }
+
+        self.pos = self.haystack.len() + 1;
+        None
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nfa::State;
+    use crate::nfa_state_creator;
+
+    #[test]
+    fn test_find_nfa() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // ab+|c
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            let s_3 = new_state!(true);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(Some('b'), Some(s_2));
+            s_2.push_transition(Some('b'), None);
+            s_0.push_transition(Some('c'), Some(s_3));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let haystack: Vec<char> = "xxabbbcaab".chars().collect();
+
+        assert_eq!(nfa.find(&haystack, MatchKind::LeftmostFirst), Some(2..6));
+        assert_eq!(nfa.find(&haystack, MatchKind::LeftmostLongest), Some(2..6));
+        assert_eq!(nfa.find(&haystack, MatchKind::Earliest), Some(2..4));
+        assert_eq!(nfa.find_iter(&haystack, MatchKind::LeftmostFirst).collect::<Vec<_>>(), vec![2..6, 6..7, 8..10]);
+        assert_eq!(nfa.find_iter(&haystack, MatchKind::Earliest).collect::<Vec<_>>(), vec![2..4, 6..7, 8..10]);
+        assert_eq!(nfa.find(&['x', 'a', 'x'], MatchKind::LeftmostFirst), None);
+
+        let dfa = nfa.as_dfa(&arena);
+        assert_eq!(dfa.find_iter(&haystack, MatchKind::LeftmostLongest).collect::<Vec<_>>(), vec![2..6, 6..7, 8..10]);
+
+        // A searcher reuses its reverse DFA across haystacks.
+        let searcher = dfa.searcher(MatchKind::LeftmostFirst);
+        assert_eq!(searcher.find(&haystack), Some(2..6));
+        assert_eq!(searcher.find_iter(&['c', 'a', 'b']).collect::<Vec<_>>(), vec![0..1, 1..3]);
+    }
+
+    #[test]
+    fn test_find_kinds() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // a+
+        let s_0 = new_state!();
+        let s_1 = new_state!(true, &[(Some('a'), None)]);
+        s_0.push_transition(Some('a'), Some(s_1));
+        let dfa = Nfa::new(s_0).as_dfa(&arena);
+
+        let haystack: Vec<char> = "aaa".chars().collect();
+        assert_eq!(dfa.find(&haystack, MatchKind::LeftmostFirst), Some(0..3));
+        assert_eq!(dfa.find(&haystack, MatchKind::LeftmostLongest), Some(0..3));
+        assert_eq!(dfa.find_iter(&haystack, MatchKind::Earliest).collect::<Vec<_>>(), vec![0..1, 1..2, 2..3]);
+
+        // abcd|c, where the match ending first is not the leftmost one
+        let s_0 = new_state!();
+        let s_1 = new_state!();
+        let s_2 = new_state!();
+        let s_3 = new_state!();
+        let s_4 = new_state!(true);
+        let s_5 = new_state!(true);
+        s_0.push_transition(Some('a'), Some(s_1));
+        s_1.push_transition(Some('b'), Some(s_2));
+        s_2.push_transition(Some('c'), Some(s_3));
+        s_3.push_transition(Some('d'), Some(s_4));
+        s_0.push_transition(Some('c'), Some(s_5));
+        let nfa = Nfa::new(s_0);
+
+        let haystack: Vec<char> = "xabcd".chars().collect();
+        assert_eq!(nfa.find(&haystack, MatchKind::LeftmostFirst), Some(1..5));
+        assert_eq!(nfa.find(&haystack, MatchKind::LeftmostLongest), Some(1..5));
+        assert_eq!(nfa.find(&haystack, MatchKind::Earliest), Some(3..4));
+    }
+
+    #[test]
+    fn test_find_patterns() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        let a = || Nfa::new(&*new_state!(false, &[(Some('a'), Some(new_state!(true)))]));
+        let ab = || {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(Some('b'), Some(new_state!(true)));
+            Nfa::new(&*s_0)
+        };
+        let haystack: Vec<char> = "xab".chars().collect();
+
+        // The first pattern wins among the matches at the leftmost position, even when a later one is longer.
+        let short_first = Nfa::union_patterns(&[a(), ab()], &arena);
+        assert_eq!(short_first.find(&haystack, MatchKind::LeftmostFirst), Some(1..2));
+        assert_eq!(short_first.find(&haystack, MatchKind::LeftmostLongest), Some(1..3));
+
+        let long_first = Nfa::union_patterns(&[ab(), a()], &arena);
+        assert_eq!(long_first.find(&haystack, MatchKind::LeftmostFirst), Some(1..3));
+        assert_eq!(long_first.find(&haystack, MatchKind::LeftmostLongest), Some(1..3));
+    }
+
+    #[test]
+    fn test_find_empty_matches() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // 1*
+        let start_node = new_state!(true, &[(Some(1), None)]);
+
+        let dfa = Nfa::new(start_node).as_dfa(&arena);
+        // The empty match at 3, right where 1..3 ended, is skipped.
+        assert_eq!(dfa.find_iter(&[0, 1, 1, 0], MatchKind::LeftmostLongest).collect::<Vec<_>>(), vec![0..0, 1..3, 4..4]);
+        assert_eq!(dfa.find_iter(&[0, 1, 1, 0], MatchKind::LeftmostFirst).collect::<Vec<_>>(), vec![0..0, 1..3, 4..4]);
+        assert_eq!(dfa.find(&[], MatchKind::Earliest), Some(0..0));
+    }
+}
@@ -0,0 +1,501 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::hash::Hash;
+use std::ptr::NonNull;
+
+use corrida::Corrida;
+use smallvec::Array;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::dfa::{Dfa, State};
+use crate::graph;
+use crate::nfa::{Nfa, State as NfaState};
+use crate::PatternId;
+
+const DFA_MAGIC: &[u8; 4] = b"GRBD";
+const NFA_MAGIC: &[u8; 4] = b"GRBN";
+const FORMAT_VERSION: u8 = 1;
+
+/// Encodes symbols for the binary format, every symbol must decode back to itself.
+pub trait SymbolCodec: Sized {
+    /// Appends the encoded symbol to the output.
+    fn encode(&self, out: &mut Vec<u8>);
+    /// Decodes a symbol from the front of the input, advancing past it. Returns None if the input does not start with a valid symbol.
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+/// Splits the first `N` bytes off the input.
+fn take<const N: usize>(input: &mut &[u8]) -> Option<[u8; N]> {
+    let (bytes, rest) = input.split_first_chunk::<N>()?;
+    *input = rest;
+    Some(*bytes)
+}
+
+macro_rules! impl_symbol_codec_int {
+    ($($int:ty),*) => {
+        $(
+            impl SymbolCodec for $int {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Option<Self> {
+                    take(input).map(<$int>::from_le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_symbol_codec_int!(u8, u16, u32, u64);
+
+impl SymbolCodec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match u8::decode(input)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl SymbolCodec for char {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        char::from_u32(u32::decode(input)?)
+    }
+}
+
+/// An error found while decoding or loading a serialized automaton.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// The input does not start with the magic bytes of the expected automaton kind.
+    BadMagic,
+    /// The input was written by an unsupported version of the format.
+    UnsupportedVersion(u8),
+    /// The input ended in the middle of the automaton.
+    UnexpectedEnd,
+    /// A symbol or flag could not be decoded.
+    InvalidSymbol,
+    /// The input continues after the automaton.
+    TrailingBytes,
+    /// The automaton has no states, so there is no start state.
+    NoStates,
+    /// A transition points to a state which does not exist.
+    InvalidTarget {
+        /// The state the transition leaves from.
+        state: usize,
+        /// The missing target state.
+        target: usize,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::BadMagic => write!(f, "input is not a serialized automaton of the expected kind"),
+            LoadError::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+            LoadError::UnexpectedEnd => write!(f, "input ended unexpectedly"),
+            LoadError::InvalidSymbol => write!(f, "invalid symbol or flag"),
+            LoadError::TrailingBytes => write!(f, "unexpected bytes after the automaton"),
+            LoadError::NoStates => write!(f, "automaton has no states"),
+            LoadError::InvalidTarget { state, target } => write!(f, "transition from state {} to missing state {}", state, target),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// Reads a little endian u32 length or id as a usize.
+fn read_len(input: &mut &[u8]) -> Result<usize, LoadError> {
+    u32::decode(input).map(|len| len as usize).ok_or(LoadError::UnexpectedEnd)
+}
+
+/// Checks the magic bytes and the version at the front of the input.
+fn read_header(input: &mut &[u8], magic: &[u8; 4]) -> Result<(), LoadError> {
+    if take::<4>(input).ok_or(LoadError::UnexpectedEnd)? != *magic {
+        return Err(LoadError::BadMagic);
+    }
+    match u8::decode(input).ok_or(LoadError::UnexpectedEnd)? {
+        FORMAT_VERSION => Ok(()),
+        version => Err(LoadError::UnsupportedVersion(version)),
+    }
+}
+
+// MARK: DfaData
+/// A state of a serialized DFA.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DfaStateData<Σ> {
+    /// The sorted patterns matched in the state, empty if it does not accept.
+    pub patterns: Vec<PatternId>,
+    /// The outgoing transitions, as symbols and target state numbers.
+    pub transitions: Vec<(Σ, usize)>,
+}
+
+/// A DFA as a list of numbered states instead of a pointer graph, state 0 is the start state.
+/// States are numbered in breadth first order from the start node, like in `Dfa::to_dot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DfaData<Σ> {
+    /// The states of the DFA.
+    pub states: Vec<DfaStateData<Σ>>,
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Returns the numbered representation of the DFA, which can be serialized.
+    pub fn to_data(&self) -> DfaData<Σ> {
+        let ids = self.state_ids();
+        let states = self.states().into_iter()
+            .map(|state| DfaStateData {
+                patterns: state.patterns().to_vec(),
+                transitions: state.transitions().map(|(symbol, target)| (symbol, ids[&(target as *const S)])).collect(),
+            })
+            .collect();
+
+        DfaData {
+            states
+        }
+    }
+}
+
+impl<Σ: Eq + Hash + Copy> DfaData<Σ> {
+    /// Rebuilds the pointer graph in the arena, returning the DFA starting at state 0.
+    pub fn load<'b, S: State<Σ> + Default>(&self, arena: &'b Corrida) -> Result<Dfa<'b, Σ, S>, LoadError> {
+        if self.states.is_empty() {
+            return Err(LoadError::NoStates);
+        }
+        for (state, data) in self.states.iter().enumerate() {
+            if let Some(&(_, target)) = data.transitions.iter().find(|&&(_, target)| target >= self.states.len()) {
+                return Err(LoadError::InvalidTarget { state, target });
+            }
+        }
+
+        let states: Vec<NonNull<S>> = self.states.iter().map(|data| {
+            let state = arena.alloc(S::default());
+            state.set_patterns(&data.patterns);
+            NonNull::from(state)
+        }).collect();
+
+        let edges = self.states.iter().enumerate()
+            .flat_map(|(id, data)| data.transitions.iter().map(move |&(symbol, target)| (id, symbol, target)));
+        // Safety, the states were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&states, edges, |state, symbol, target| state.add_transition((symbol, target))) };
+
+        // Safety, state 0 was allocated in the arena, and lives as long as it.
+        Ok(Dfa::from_start(unsafe { &*states[0].as_ptr() }))
+    }
+}
+
+impl<Σ: SymbolCodec> DfaData<Σ> {
+    /// Encodes the DFA in the stable binary format.
+    ///
+    /// The format is the magic bytes `GRBD`, a version byte, and the state count followed by every state.
+    /// A state is its pattern count and patterns, then its transition count and transitions as symbol and target pairs. Integers are little endian u32.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::from(DFA_MAGIC.as_slice());
+        out.push(FORMAT_VERSION);
+        (self.states.len() as u32).encode(&mut out);
+        for state in &self.states {
+            (state.patterns.len() as u32).encode(&mut out);
+            state.patterns.iter().for_each(|pattern| pattern.encode(&mut out));
+            (state.transitions.len() as u32).encode(&mut out);
+            for (symbol, target) in &state.transitions {
+                symbol.encode(&mut out);
+                (*target as u32).encode(&mut out);
+            }
+        }
+        out
+    }
+
+    /// Decodes a DFA written by `to_bytes`. Targets are checked when the DFA is loaded.
+    pub fn from_bytes(mut input: &[u8]) -> Result<Self, LoadError> {
+        let input = &mut input;
+        read_header(input, DFA_MAGIC)?;
+
+        let count = read_len(input)?;
+        let mut states = Vec::new();
+        for _ in 0..count {
+            let patterns = (0..read_len(input)?)
+                .map(|_| u32::decode(input).ok_or(LoadError::UnexpectedEnd))
+                .collect::<Result<_, _>>()?;
+            let transitions = (0..read_len(input)?)
+                .map(|_| Ok((Σ::decode(input).ok_or(LoadError::InvalidSymbol)?, read_len(input)?)))
+                .collect::<Result<_, _>>()?;
+            states.push(DfaStateData {
+                patterns,
+                transitions
+            });
+        }
+
+        if !input.is_empty() {
+            return Err(LoadError::TrailingBytes);
+        }
+        Ok(Self {
+            states
+        })
+    }
+}
+
+// MARK: NfaData
+/// A state of a serialized NFA.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NfaStateData<Σ> {
+    /// The pattern accepted in the state, if it accepts.
+    pub accept: Option<PatternId>,
+    /// The outgoing transitions, as symbols and target state numbers. None symbols are epsilon transitions.
+    pub transitions: Vec<(Option<Σ>, usize)>,
+}
+
+/// An NFA as a list of numbered states instead of a pointer graph, state 0 is the start state.
+/// States are numbered in breadth first order from the start node, like in `Nfa::to_dot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NfaData<Σ> {
+    /// The states of the NFA.
+    pub states: Vec<NfaStateData<Σ>>,
+}
+
+impl<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> Nfa<'a, NfaState<TARGETS_HINT, Σ>>
+where
+    [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+{
+    /// Returns the numbered representation of the NFA, which can be serialized.
+    pub fn to_data(&self) -> NfaData<Σ> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let states = states.into_iter()
+            .map(|state| NfaStateData {
+                accept: state.pattern(),
+                transitions: state.transitions().map(|(symbol, target)| (symbol, ids[&(target as *const NfaState<TARGETS_HINT, Σ>)])).collect(),
+            })
+            .collect();
+
+        NfaData {
+            states
+        }
+    }
+}
+
+impl<Σ: Eq + Hash + Copy> NfaData<Σ> {
+    /// Rebuilds the pointer graph in the arena, returning the NFA starting at state 0.
+    pub fn load<'b, const TARGETS_HINT: usize>(&self, arena: &'b Corrida) -> Result<Nfa<'b, NfaState<TARGETS_HINT, Σ>>, LoadError>
+    where
+        [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+    {
+        if self.states.is_empty() {
+            return Err(LoadError::NoStates);
+        }
+        for (state, data) in self.states.iter().enumerate() {
+            if let Some(&(_, target)) = data.transitions.iter().find(|&&(_, target)| target >= self.states.len()) {
+                return Err(LoadError::InvalidTarget { state, target });
+            }
+        }
+
+        let states: Vec<NonNull<NfaState<TARGETS_HINT, Σ>>> = self.states.iter().map(|data| {
+            let state = arena.alloc(NfaState::new(false));
+            if let Some(pattern) = data.accept {
+                state.set_pattern(pattern);
+            }
+            NonNull::from(state)
+        }).collect();
+
+        let edges = self.states.iter().enumerate()
+            .flat_map(|(id, data)| data.transitions.iter().map(move |&(symbol, target)| (id, symbol, target)));
+        // Safety, the states were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&states, edges, |state, symbol, target| state.push_transition(symbol, target)) };
+
+        // Safety, state 0 was allocated in the arena, and lives as long as it.
+        Ok(Nfa::new(unsafe { &*states[0].as_ptr() }))
+    }
+}
+
+impl<Σ: SymbolCodec> NfaData<Σ> {
+    /// Encodes the NFA in the stable binary format.
+    ///
+    /// The format is the magic bytes `GRBN`, a version byte, and the state count followed by every state.
+    /// A state is an accept flag byte, followed by the pattern if set, then its transition count and transitions.
+    /// A transition is a flag byte which is 0 for epsilon transitions, the symbol if the flag is 1, and the target. Integers are little endian u32.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::from(NFA_MAGIC.as_slice());
+        out.push(FORMAT_VERSION);
+        (self.states.len() as u32).encode(&mut out);
+        for state in &self.states {
+            state.accept.is_some().encode(&mut out);
+            if let Some(pattern) = state.accept {
+                pattern.encode(&mut out);
+            }
+            (state.transitions.len() as u32).encode(&mut out);
+            for (symbol, target) in &state.transitions {
+                symbol.is_some().encode(&mut out);
+                if let Some(symbol) = symbol {
+                    symbol.encode(&mut out);
+                }
+                (*target as u32).encode(&mut out);
+            }
+        }
+        out
+    }
+
+    /// Decodes an NFA written by `to_bytes`. Targets are checked when the NFA is loaded.
+    pub fn from_bytes(mut input: &[u8]) -> Result<Self, LoadError> {
+        let input = &mut input;
+        read_header(input, NFA_MAGIC)?;
+
+        let count = read_len(input)?;
+        let mut states = Vec::new();
+        for _ in 0..count {
+            let accept = match bool::decode(input).ok_or(LoadError::InvalidSymbol)? {
+                true => Some(u32::decode(input).ok_or(LoadError::UnexpectedEnd)?),
+                false => None,
+            };
+            let transitions = (0..read_len(input)?)
+                .map(|_| {
+                    let symbol = match bool::decode(input).ok_or(LoadError::InvalidSymbol)? {
+                        true => Some(Σ::decode(input).ok_or(LoadError::InvalidSymbol)?),
+                        false => None,
+                    };
+                    Ok((symbol, read_len(input)?))
+                })
+                .collect::<Result<_, _>>()?;
+            states.push(NfaStateData {
+                accept,
+                transitions
+            });
+        }
+
+        if !input.is_empty() {
+            return Err(LoadError::TrailingBytes);
+        }
+        Ok(Self {
+            states
+        })
+    }
+}
+
+// MARK: JSON
+#[cfg(feature = "serde")]
+impl<Σ: Serialize + for<'de> Deserialize<'de>> DfaData<Σ> {
+    /// Encodes the DFA as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("DFA data always serializes.")
+    }
+
+    /// Decodes a DFA written by `to_json`. Targets are checked when the DFA is loaded.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<Σ: Serialize + for<'de> Deserialize<'de>> NfaData<Σ> {
+    /// Encodes the NFA as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("NFA data always serializes.")
+    }
+
+    /// Decodes an NFA written by `to_json`. Targets are checked when the NFA is loaded.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dfa::{CompleteState, PartialState};
+    use crate::nfa::State;
+    use crate::nfa_state_creator;
+
+    #[test]
+    fn test_dfa_round_trip() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, char, 2);
+
+        // (ab)*c, as a pattern with id 3.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!();
+            s_2.set_pattern(3);
+            s_0.push_transition(Some('a'), Some(s_1));
+            s_1.push_transition(Some('b'), Some(s_0));
+            s_0.push_transition(Some('c'), Some(s_2));
+            s_0
+        };
+
+        let dfa = Nfa::new(start_node).as_dfa(&arena);
+        let data = dfa.to_data();
+
+        let bytes = data.to_bytes();
+        assert_eq!(&bytes[..4], b"GRBD");
+        let decoded = DfaData::<char>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, data);
+
+        let loaded_arena = Corrida::new(None);
+        let loaded = decoded.load::<PartialState<char>>(&loaded_arena).unwrap();
+        assert_eq!(loaded.to_data(), data);
+        for input in ["c", "abc", "ababc", "ab", "abac", ""] {
+            let input: Vec<char> = input.chars().collect();
+            assert_eq!(loaded.matching_patterns(&input), dfa.matching_patterns(&input));
+        }
+
+        let complete = DfaData::<bool>::from_bytes(&DfaData { states: vec![DfaStateData { patterns: vec![0], transitions: vec![(true, 0)] }] }.to_bytes())
+            .unwrap()
+            .load::<CompleteState<bool>>(&loaded_arena)
+            .unwrap();
+        assert_eq!(complete.try_simulate(&[true, true]), Ok(true));
+
+        assert_eq!(DfaData::<char>::from_bytes(&bytes[..bytes.len() - 1]), Err(LoadError::UnexpectedEnd));
+        assert_eq!(DfaData::<char>::from_bytes(b"GRBN\x01\0\0\0\0"), Err(LoadError::BadMagic));
+        let broken = DfaData { states: vec![DfaStateData { patterns: vec![], transitions: vec![('a', 2)] }] };
+        assert_eq!(broken.load::<PartialState<char>>(&loaded_arena).err(), Some(LoadError::InvalidTarget { state: 0, target: 2 }));
+    }
+
+    #[test]
+    fn test_nfa_round_trip() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // 1*0, with the loop behind an epsilon transition.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(false, &[(Some(1), None)]);
+            let s_2 = new_state!(true);
+            s_0.push_transition(None, Some(s_1));
+            s_1.push_transition(Some(0), Some(s_2));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+        let data = nfa.to_data();
+        let decoded = NfaData::<u8>::from_bytes(&data.to_bytes()).unwrap();
+        assert_eq!(decoded, data);
+
+        let loaded_arena = Corrida::new(None);
+        let loaded = decoded.load::<2>(&loaded_arena).unwrap();
+        assert_eq!(loaded.to_data(), data);
+        for input in [&[0u8][..], &[1, 1, 0], &[1], &[0, 0]] {
+            assert_eq!(loaded.simulate_slice(input), nfa.simulate_slice(input));
+        }
+
+        #[cfg(feature = "serde")]
+        {
+            let json = data.to_json();
+            assert_eq!(NfaData::<u8>::from_json(&json).unwrap(), data);
+            assert_eq!(DfaData::<u8>::from_json(&nfa.as_dfa(&arena).to_data().to_json()).unwrap(), nfa.as_dfa(&arena).to_data());
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+use std::fmt::{Debug, Write};
+use std::hash::Hash;
+use std::ptr::NonNull;
+
+use smallvec::Array;
+
+use crate::dfa::{Dfa, State};
+use crate::graph;
+use crate::nfa::{Nfa, State as NfaState};
+
+/// One consumed symbol of a traced run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep<Σ> {
+    /// The position of the symbol in the input.
+    pub position: usize,
+    /// The symbol that was read.
+    pub symbol: Σ,
+    /// The ids of the states active before reading the symbol, sorted.
+    pub before: Vec<usize>,
+    /// The ids of the states active after reading the symbol, sorted. Empty if no active state had a transition on the symbol.
+    pub after: Vec<usize>,
+    /// The ids in `after` from which no accept state can be reached, sorted. The run dies once every state in `after` is dead.
+    pub dead: Vec<usize>,
+}
+
+/// The record of a run over some input, state ids are the same as the ones used by `to_dot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace<Σ> {
+    /// Every consumed symbol, the trace stops after the symbol that killed the run, and is empty if the start is dead.
+    pub steps: Vec<TraceStep<Σ>>,
+    /// The position of the symbol after which no accept state could be reached anymore, 0 if none can be reached from the start. None if the run survived the input.
+    pub dead_at: Option<usize>,
+    /// Whether the input was accepted.
+    pub accepted: bool,
+}
+
+/// Escapes a symbol for use inside a quoted DOT label.
+fn dot_label(symbol: &impl Debug) -> String {
+    format!("{:?}", symbol).replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<'a, Σ: Eq + Hash + Copy, S: State<Σ>> Dfa<'a, Σ, S> {
+    /// Runs the DFA over the input, recording the state before and after every symbol.
+    /// The run dies when a transition is missing, or leads to a state from which no accept state can be reached. A run with a dead start reads nothing.
+    pub fn trace(&self, input: &[Σ]) -> Trace<Σ> {
+        let ids = self.state_ids();
+        let live = self.live_states();
+
+        let mut steps = Vec::with_capacity(input.len());
+        let mut cur = self.start_node;
+        let mut dead_at = (!live.contains(&(cur as *const S))).then_some(0);
+
+        if dead_at.is_none() {
+            for (position, &symbol) in input.iter().enumerate() {
+                let next = cur.step(symbol);
+                let after: Vec<usize> = next.map(|next| ids[&(next as *const S)]).into_iter().collect();
+                let next = next.filter(|&next| live.contains(&(next as *const S)));
+                steps.push(TraceStep {
+                    position,
+                    symbol,
+                    before: vec![ids[&(cur as *const S)]],
+                    dead: if next.is_some() { Vec::new() } else { after.clone() },
+                    after,
+                });
+
+                match next {
+                    Some(next) => cur = next,
+                    None => {
+                        dead_at = Some(position);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Trace {
+            steps,
+            dead_at,
+            accepted: dead_at.is_none() && cur.is_accept(),
+        }
+    }
+
+    /// Renders the DFA in the graphviz DOT format, accept states are drawn with a double circle.
+    pub fn to_dot(&self) -> String
+    where
+        Σ: Debug,
+    {
+        let states = self.states();
+        let ids = self.state_ids();
+
+        let mut dot = String::from("digraph {\n    rankdir=LR;\n    start [shape=point];\n    start -> 0;\n");
+        for (id, state) in states.iter().enumerate() {
+            let shape = if state.is_accept() { "doublecircle" } else { "circle" };
+            writeln!(dot, "    {id} [shape={shape}];").unwrap();
+            for (symbol, target) in state.transitions() {
+                writeln!(dot, "    {id} -> {} [label=\"{}\"];", ids[&(target as *const S)], dot_label(&symbol)).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<'a, const TARGETS_HINT: usize, Σ: Eq + Hash + Copy> Nfa<'a, NfaState<TARGETS_HINT, Σ>>
+where
+    [NonNull<NfaState<TARGETS_HINT, Σ>>; TARGETS_HINT]: Array<Item = NonNull<NfaState<TARGETS_HINT, Σ>>>,
+{
+    /// Runs the NFA over the input, recording the full epsilon closure of the active states before and after every symbol.
+    /// Like the DFA trace, the run dies when no active state can reach an accept state anymore. A run with a dead start reads nothing.
+    pub fn trace(&self, input: &[Σ]) -> Trace<Σ> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let targets: Vec<Vec<usize>> = states.iter()
+            .map(|state| state.transitions().map(|(_, next)| ids[&(next as *const NfaState<TARGETS_HINT, Σ>)]).collect())
+            .collect();
+        let accepting: Vec<bool> = states.iter().map(|state| state.is_accept()).collect();
+        let live = graph::co_reachable(&targets, &accepting);
+
+        let closure = |mut active: Vec<usize>| {
+            let mut seen: HashSet<usize> = active.iter().copied().collect();
+            let mut i = 0;
+            while i < active.len() {
+                for next in states[active[i]].get_transitions(None) {
+                    let next = ids[&(next as *const NfaState<TARGETS_HINT, Σ>)];
+                    if seen.insert(next) {
+                        active.push(next);
+                    }
+                }
+                i += 1;
+            }
+            active.sort_unstable();
+            active
+        };
+
+        let mut steps = Vec::with_capacity(input.len());
+        let mut current = closure(vec![0]);
+        let mut dead_at = (!current.iter().any(|&id| live[id])).then_some(0);
+
+        if dead_at.is_none() {
+            for (position, &symbol) in input.iter().enumerate() {
+                let mut next: Vec<usize> = current.iter()
+                    .flat_map(|&id| states[id].get_transitions(Some(symbol)))
+                    .map(|next| ids[&(next as *const NfaState<TARGETS_HINT, Σ>)])
+                    .collect();
+                next.sort_unstable();
+                next.dedup();
+                let next = closure(next);
+
+                let dead: Vec<usize> = next.iter().copied().filter(|&id| !live[id]).collect();
+                let died = dead.len() == next.len();
+                steps.push(TraceStep {
+                    position,
+                    symbol,
+                    before: current,
+                    after: next.clone(),
+                    dead,
+                });
+                current = next;
+
+                if died {
+                    dead_at = Some(position);
+                    break;
+                }
+            }
+        }
+
+        Trace {
+            steps,
+            dead_at,
+            accepted: current.iter().any(|&id| states[id].is_accept()),
+        }
+    }
+
+    /// Renders the NFA in the graphviz DOT format, accept states are drawn with a double circle and epsilon transitions are labelled ε.
+    pub fn to_dot(&self) -> String
+    where
+        Σ: Debug,
+    {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let mut dot = String::from("digraph {\n    rankdir=LR;\n    start [shape=point];\n    start -> 0;\n");
+        for (id, state) in states.iter().enumerate() {
+            let shape = if state.is_accept() { "doublecircle" } else { "circle" };
+            writeln!(dot, "    {id} [shape={shape}];").unwrap();
+            for (symbol, target) in state.transitions() {
+                let label = symbol.map(|symbol| dot_label(&symbol)).unwrap_or_else(|| String::from("ε"));
+                writeln!(dot, "    {id} -> {} [label=\"{label}\"];", ids[&(target as *const NfaState<TARGETS_HINT, Σ>)]).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dfa::{PartialState, State as _};
+    use crate::nfa::State;
+    use crate::{dfa_state_creator, nfa_state_creator};
+    use corrida::Corrida;
+
+    #[test]
+    fn test_dfa_trace() {
+        let arena = Corrida::new(None);
+        dfa_state_creator!(($), new_state, arena, PartialState<char>);
+
+        // Accepts "ab", 'c' leads into a state that can never accept.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!();
+            let s_2 = new_state!(true);
+            let trap = new_state!();
+            s_0.add_transition(('a', Some(s_1)));
+            s_1.add_transition(('b', Some(s_2)));
+            s_1.add_transition(('c', Some(trap)));
+            s_0
+        };
+
+        let dfa = Dfa::<char, PartialState<char>>::new(start_node);
+
+        let trace = dfa.trace(&['a', 'b']);
+        assert!(trace.accepted);
+        assert_eq!(trace.dead_at, None);
+        assert_eq!(trace.steps.iter().map(|step| (step.before.clone(), step.after.clone())).collect::<Vec<_>>(), vec![(vec![0], vec![1]), (vec![1], vec![2])]);
+
+        let trace = dfa.trace(&['a', 'c', 'b']);
+        assert!(!trace.accepted);
+        assert_eq!(trace.dead_at, Some(1));
+        assert_eq!(trace.steps.len(), 2);
+        assert_eq!(trace.steps[1].after, vec![3]);
+        assert_eq!(trace.steps[1].dead, vec![3]);
+
+        let trace = dfa.trace(&['b']);
+        assert_eq!(trace.dead_at, Some(0));
+        assert!(trace.steps[0].after.is_empty());
+
+        // No accept state is reachable from the start, so the run is dead before reading anything.
+        let dead = Dfa::<char, PartialState<char>>::new(new_state!(false, &[('a', None)]));
+        assert_eq!(dead.trace(&['a']), Trace { steps: Vec::new(), dead_at: Some(0), accepted: false });
+
+        let dot = dfa.to_dot();
+        assert!(dot.contains("0 -> 1 [label=\"'a'\"];"));
+        assert!(dot.contains("2 [shape=doublecircle];"));
+        assert!(dot.contains("1 -> 3 [label=\"'c'\"];"));
+    }
+
+    #[test]
+    fn test_nfa_trace() {
+        let arena = Corrida::new(None);
+        nfa_state_creator!(($), new_state, arena, u8, 2);
+
+        // a*b, with the loop behind an epsilon transition, 'c' leads into a state that can never accept.
+        let start_node = {
+            let s_0 = new_state!();
+            let s_1 = new_state!(false, &[(Some(b'a'), None)]);
+            let s_2 = new_state!(true);
+            let trap = new_state!();
+            s_0.push_transition(None, Some(s_1));
+            s_1.push_transition(Some(b'b'), Some(s_2));
+            s_1.push_transition(Some(b'c'), Some(trap));
+            s_0
+        };
+
+        let nfa = Nfa::new(start_node);
+
+        let trace = nfa.trace(b"aab");
+        assert!(trace.accepted);
+        assert_eq!(trace.steps[0].before, vec![0, 1]);
+        assert_eq!(trace.steps[0].after, vec![1]);
+        assert_eq!(trace.steps[2].after, nfa.accept_states());
+
+        let trace = nfa.trace(b"abb");
+        assert!(!trace.accepted);
+        assert_eq!(trace.dead_at, Some(2));
+        assert_eq!(trace.steps.len(), 3);
+        assert!(trace.steps[2].after.is_empty());
+
+        // The trap state is reported in the closure, and marked dead.
+        let trace = nfa.trace(b"acb");
+        assert_eq!(trace.dead_at, Some(1));
+        assert_eq!(trace.steps.len(), 2);
+        assert_eq!(trace.steps[1].after.len(), 1);
+        assert_eq!(trace.steps[1].dead, trace.steps[1].after);
+
+        // No accept state is reachable from the start, so the run is dead before reading anything.
+        let dead = Nfa::new(&*new_state!(false, &[(Some(b'a'), None)]));
+        assert_eq!(dead.trace(b"a"), Trace { steps: Vec::new(), dead_at: Some(0), accepted: false });
+
+        let dot = nfa.to_dot();
+        assert!(dot.contains("0 -> 1 [label=\"ε\"];"));
+        assert!(dot.contains("1 -> 1 [label=\"97\"];"));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ptr::NonNull;
+
+use corrida::Corrida;
+use smallmap::Map;
+
+use crate::graph;
+
+// MARK: MooreState
+/// A node in a Moore machine, every state carries the output emitted when it is entered.
+pub struct MooreState<Σ: Eq + Hash + Copy, O> {
+    transitions: Map<Σ, NonNull<MooreState<Σ, O>>>,
+    output: O,
+}
+
+impl<Σ: Eq + Hash + Copy, O> MooreState<Σ, O> {
+    /// Creates a new state with no transitions, emitting `output` when entered.
+    pub fn new(output: O) -> Self {
+        Self {
+            transitions: Map::new(),
+            output
+        }
+    }
+
+    /// Inserts the provided transition. None represents a self-transition.
+    pub fn add_transition(&mut self, transition: (Σ, Option<&MooreState<Σ, O>>)) {
+        let target = transition.1.unwrap_or(self) as *const MooreState<Σ, O> as *mut MooreState<Σ, O>;
+        self.transitions.insert(transition.0, NonNull::new(target).unwrap());
+    }
+
+    /// Returns the next state after reading the symbol, if the transition exists.
+    pub fn get_transition(&self, symbol: Σ) -> Option<&MooreState<Σ, O>> {
+        // Safety, every transition points to a MooreState allocated in the same arena as this one.
+        self.transitions.get(&symbol).map(|target| unsafe { &*target.as_ptr() })
+    }
+
+    /// Returns the output emitted when this state is entered.
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    fn transitions(&self) -> impl Iterator<Item = (Σ, &MooreState<Σ, O>)> {
+        // Safety, every transition points to a MooreState allocated in the same arena as this one.
+        self.transitions.iter().map(|(symbol, target)| (*symbol, unsafe { &*target.as_ptr() }))
+    }
+}
+
+// MARK: MealyState
+/// A node in a Mealy machine, every transition carries the output emitted when it is taken.
+pub struct MealyState<Σ: Eq + Hash + Copy, O> {
+    transitions: Map<Σ, (NonNull<MealyState<Σ, O>>, O)>,
+}
+
+impl<Σ: Eq + Hash + Copy, O> MealyState<Σ, O> {
+    /// Creates a new state with no transitions.
+    pub fn new() -> Self {
+        Self {
+            transitions: Map::new()
+        }
+    }
+
+    /// Inserts the provided transition, emitting the output when taken. None represents a self-transition.
+    pub fn add_transition(&mut self, transition: (Σ, O, Option<&MealyState<Σ, O>>)) {
+        let target = transition.2.unwrap_or(self) as *const MealyState<Σ, O> as *mut MealyState<Σ, O>;
+        self.transitions.insert(transition.0, (NonNull::new(target).unwrap(), transition.1));
+    }
+
+    /// Returns the next state and the output emitted after reading the symbol, if the transition exists.
+    pub fn get_transition(&self, symbol: Σ) -> Option<(&MealyState<Σ, O>, &O)> {
+        // Safety, every transition points to a MealyState allocated in the same arena as this one.
+        self.transitions.get(&symbol).map(|(target, output)| (unsafe { &*target.as_ptr() }, output))
+    }
+
+    fn transitions(&self) -> impl Iterator<Item = (Σ, &MealyState<Σ, O>, &O)> {
+        // Safety, every transition points to a MealyState allocated in the same arena as this one.
+        self.transitions.iter().map(|(symbol, (target, output))| (*symbol, unsafe { &*target.as_ptr() }, output))
+    }
+}
+
+impl<Σ: Eq + Hash + Copy, O> Default for MealyState<Σ, O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates a macro for creating states in a Moore machine, taking the output and optionally the transitions.
+#[macro_export]
+macro_rules! moore_state_creator {
+    (($d:tt), $func_name: ident, $arena: expr, $state_type: ty) => {
+        macro_rules! $func_name {
+            ($output:expr $d(,$transitions: expr)? ) => {
+                {
+                    let new_state = $arena.alloc(<$state_type>::new($output));
+                    $d(
+                        let transitions: &[(_, Option<&$state_type>)] = $transitions;
+                        transitions.iter().for_each(|transition| new_state.add_transition(*transition));
+                    )?
+                    new_state
+                }
+            };
+        }
+    };
+}
+
+/// Creates a macro for creating states in a Mealy machine, optionally taking `(symbol, output, target)` transitions.
+#[macro_export]
+macro_rules! mealy_state_creator {
+    (($d:tt), $func_name: ident, $arena: expr, $state_type: ty) => {
+        macro_rules! $func_name {
+            ($d($transitions: expr)? ) => {
+                {
+                    let new_state = $arena.alloc(<$state_type>::new());
+                    $d(
+                        let transitions: &[(_, _, Option<&$state_type>)] = $transitions;
+                        transitions.iter().for_each(|(symbol, output, target)| new_state.add_transition((*symbol, output.clone(), *target)));
+                    )?
+                    new_state
+                }
+            };
+        }
+    };
+}
+
+// MARK: Moore
+/// A Moore machine, a DFA whose states emit an output every time they are entered.
+pub struct Moore<'a, Σ: Eq + Hash + Copy, O> {
+    start_node: &'a MooreState<Σ, O>,
+}
+
+impl<'a, Σ: Eq + Hash + Copy, O: Clone> Moore<'a, Σ, O> {
+    /// Creates a new Moore machine with the given start node.
+    pub fn new(start_node: &'a MooreState<Σ, O>) -> Self {
+        Self {
+            start_node
+        }
+    }
+
+    /// Returns the output of the start node, emitted before any input is read.
+    pub fn start_output(&self) -> &'a O {
+        self.start_node.output()
+    }
+
+    /// Returns an iterator over the outputs of the states entered while reading the input, one per symbol.
+    /// The iterator ends early when a transition is missing.
+    pub fn transduce_iter(&self, input: impl Iterator<Item = Σ>) -> impl Iterator<Item = O> {
+        let mut cur = self.start_node;
+        input.map_while(move |symbol| {
+            let next = cur.get_transition(symbol)?;
+            cur = next;
+            Some(next.output().clone())
+        })
+    }
+
+    /// Returns the outputs of the states entered while reading the input, stopping at the first missing transition.
+    pub fn transduce_slice(&self, input: &[Σ]) -> Vec<O> {
+        self.transduce_iter(input.iter().copied()).collect()
+    }
+
+    fn states(&self) -> Vec<&'a MooreState<Σ, O>> {
+        graph::reachable(self.start_node, |state| state.transitions().map(|(_, next)| next))
+    }
+
+    /// Builds the equivalent Mealy machine in the arena, where every transition emits the output of the state it enters.
+    /// The output of the start node is not represented, see `start_output`.
+    pub fn to_mealy<'b>(&self, arena: &'b Corrida) -> Mealy<'b, Σ, O> {
+        let states = self.states();
+        let ids = graph::state_ids(&states);
+
+        let copies: Vec<NonNull<MealyState<Σ, O>>> = states.iter().map(|_| NonNull::from(arena.alloc(MealyState::new()))).collect();
+
+        let edges = states.iter().enumerate()
+            .flat_map(|(id, state)| state.transitions().map(move |(symbol, target)| (id, symbol, target)))
+            .map(|(id, symbol, target)| (id, (symbol, target.output().clone()), ids[&(target as *const MooreState<Σ, O>)]));
+        // Safety, the copies were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&copies, edges, |copy, (symbol, output), target| copy.add_transition((symbol, output, target))) };
+
+        // Safety, the start node is the first state in breadth first order, and lives as long as the arena.
+        Mealy::new(unsafe { &*copies[0].as_ptr() })
+    }
+}
+
+// MARK: Mealy
+/// A Mealy machine, a DFA whose transitions emit an output every time they are taken.
+pub struct Mealy<'a, Σ: Eq + Hash + Copy, O> {
+    start_node: &'a MealyState<Σ, O>,
+}
+
+impl<'a, Σ: Eq + Hash + Copy, O: Clone> Mealy<'a, Σ, O> {
+    /// Creates a new Mealy machine with the given start node.
+    pub fn new(start_node: &'a MealyState<Σ, O>) -> Self {
+        Self {
+            start_node
+        }
+    }
+
+    /// Returns an iterator over the outputs of the transitions taken while reading the input, one per symbol.
+    /// The iterator ends early when a transition is missing.
+    pub fn transduce_iter(&self, input: impl Iterator<Item = Σ>) -> impl Iterator<Item = O> {
+        let mut cur = self.start_node;
+        input.map_while(move |symbol| {
+            let (next, output) = cur.get_transition(symbol)?;
+            cur = next;
+            Some(output.clone())
+        })
+    }
+
+    /// Returns the outputs of the transitions taken while reading the input, stopping at the first missing transition.
+    pub fn transduce_slice(&self, input: &[Σ]) -> Vec<O> {
+        self.transduce_iter(input.iter().copied()).collect()
+    }
+}
+
+impl<'a, Σ: Eq + Hash + Copy, O: Clone + Eq + Hash> Mealy<'a, Σ, O> {
+    /// Builds the equivalent Moore machine in the arena, with one state per pair of Mealy state and output entering it.
+    /// The start node emits `start_output`, since a Mealy machine has no output before reading input.
+    pub fn to_moore<'b>(&self, start_output: O, arena: &'b Corrida) -> Moore<'b, Σ, O> {
+        let start = (self.start_node as *const MealyState<Σ, O>, start_output.clone());
+        let mut ids = HashMap::from([(start, 0)]);
+        let mut pairs = vec![(self.start_node, start_output)];
+
+        let mut edges = Vec::new();
+        let mut i = 0;
+        while i < pairs.len() {
+            let (state, _) = pairs[i];
+            for (symbol, target, output) in state.transitions() {
+                let next = pairs.len();
+                let target_id = *ids.entry((target as *const MealyState<Σ, O>, output.clone())).or_insert_with(|| {
+                    pairs.push((target, output.clone()));
+                    next
+                });
+                edges.push((i, symbol, target_id));
+            }
+            i += 1;
+        }
+
+        let copies: Vec<NonNull<MooreState<Σ, O>>> = pairs.into_iter().map(|(_, output)| NonNull::from(arena.alloc(MooreState::new(output)))).collect();
+        // Safety, the copies were just allocated in the arena and are only reachable through these pointers.
+        unsafe { graph::link(&copies, edges, |copy, symbol, target| copy.add_transition((symbol, target))) };
+
+        // Safety, the start node is the first state created, and lives as long as the arena.
+        Moore::new(unsafe { &*copies[0].as_ptr() })
+    }
+}
+
+// MARK: Tests
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_moore() {
+        let arena = Corrida::new(None);
+        moore_state_creator!(($), new_state, arena, MooreState<u8, &'static str>);
+
+        // Reports the parity of the number of 1s read so far, 2 is not part of the alphabet.
+        let start_node = {
+            let even = new_state!("even", &[(0, None)]);
+            let odd = new_state!("odd", &[(0, None), (1, Some(even))]);
+            even.add_transition((1, Some(odd)));
+            even
+        };
+
+        let moore = Moore::new(start_node);
+        assert_eq!(*moore.start_output(), "even");
+        assert_eq!(moore.transduce_slice(&[1, 0, 1, 1]), vec!["odd", "odd", "even", "odd"]);
+        assert_eq!(moore.transduce_slice(&[1, 2, 1]), vec!["odd"]);
+
+        let mealy = moore.to_mealy(&arena);
+        for input in [&[1u8, 0, 1, 1][..], &[], &[0, 0, 1], &[1, 2, 1]] {
+            assert_eq!(mealy.transduce_slice(input), moore.transduce_slice(input));
+        }
+    }
+
+    #[test]
+    fn test_mealy() {
+        let arena = Corrida::new(None);
+        mealy_state_creator!(($), new_state, arena, MealyState<bool, char>);
+
+        // Edge detector, emits '^' on a rising edge, 'v' on a falling edge and '-' otherwise.
+        let start_node = {
+            let low = new_state!(&[(false, '-', None)]);
+            let high = new_state!(&[(true, '-', None), (false, 'v', Some(low))]);
+            low.add_transition((true, '^', Some(high)));
+            low
+        };
+
+        let mealy = Mealy::new(start_node);
+        let input = [false, true, true, false, true];
+        assert_eq!(mealy.transduce_slice(&input), vec!['-', '^', '-', 'v', '^']);
+
+        // The high state is entered with two different outputs, so it is split in the Moore machine.
+        let moore = mealy.to_moore('-', &arena);
+        assert_eq!(moore.states().len(), 4);
+        assert_eq!(moore.transduce_slice(&input), mealy.transduce_slice(&input));
+        assert_eq!(moore.to_mealy(&arena).transduce_slice(&input), mealy.transduce_slice(&input));
+    }
+}